@@ -0,0 +1,102 @@
+// Stand-in for the backend's log stream, for working on the timeline without it.
+//
+//   node dev/stream_server.mjs [port] [replay file] [interval ms]
+//
+// Every line typed into stdin is pushed to every connected page as a log. Given a file (like a saved copy of
+// /logLoad), its lines are pushed one at a time every interval ms, looping back to the start at the end. Replayed
+// logs are stamped with the time they're sent, the timeline would ignore the repeats otherwise.
+// Point the url in src/web/stream.tsx at ws://localhost:<port> to use it.
+
+import { createServer } from "node:http";
+import { createHash } from "node:crypto";
+import { readFileSync } from "node:fs";
+import { createInterface } from "node:readline";
+
+const port = Number(process.argv[2] ?? 8089);
+const replay_file = process.argv[3];
+const interval = Number(process.argv[4] ?? 1000);
+
+// from RFC 6455, appended to the client's key to prove we speak WebSocket
+const HANDSHAKE_GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const sockets = new Set();
+
+const server = createServer((_request, response) => {
+	response.writeHead(426, { "Content-Type": "text/plain" });
+	response.end("WebSocket only\n");
+});
+
+server.on("upgrade", (request, socket) => {
+	const key = request.headers["sec-websocket-key"];
+	if (!key) {
+		socket.destroy();
+		return;
+	}
+	const accept = createHash("sha1").update(key + HANDSHAKE_GUID).digest("base64");
+	socket.write(
+		"HTTP/1.1 101 Switching Protocols\r\n" +
+			"Upgrade: websocket\r\n" +
+			"Connection: Upgrade\r\n" +
+			`Sec-WebSocket-Accept: ${accept}\r\n\r\n`,
+	);
+	sockets.add(socket);
+	console.log(`page connected (${sockets.size} open)`);
+
+	// pages never send anything we care about. Their close frame isn't answered, the page gives up waiting and
+	// drops the connection, which is all a dev server needs.
+	socket.on("data", () => {});
+	socket.on("end", () => socket.end());
+	socket.on("close", () => {
+		sockets.delete(socket);
+		console.log(`page disconnected (${sockets.size} open)`);
+	});
+	socket.on("error", () => sockets.delete(socket));
+});
+
+// A single unmasked text frame, servers never mask
+function frame(text) {
+	const payload = Buffer.from(text);
+	let header;
+	if (payload.length < 126) {
+		header = Buffer.from([0x81, payload.length]);
+	} else if (payload.length < 65536) {
+		header = Buffer.alloc(4);
+		header[0] = 0x81;
+		header[1] = 126;
+		header.writeUInt16BE(payload.length, 2);
+	} else {
+		header = Buffer.alloc(10);
+		header[0] = 0x81;
+		header[1] = 127;
+		header.writeBigUInt64BE(BigInt(payload.length), 2);
+	}
+	return Buffer.concat([header, payload]);
+}
+
+function send(text) {
+	const data = frame(text);
+	for (const socket of sockets) socket.write(data);
+}
+
+createInterface({ input: process.stdin }).on("line", (line) => {
+	if (line.trim() != "") send(line);
+});
+
+if (replay_file) {
+	const lines = readFileSync(replay_file, "utf8")
+		.split("\n")
+		.filter((line) => line.trim() != "");
+	let next = 0;
+	if (lines.length > 0) {
+		setInterval(() => {
+			// lines are "label,timestamp"
+			const line = lines[next];
+			const comma = line.indexOf(",");
+			const label = comma < 0 ? line : line.slice(0, comma);
+			send(`${label},${Date.now()}`);
+			next = (next + 1) % lines.length;
+		}, interval);
+	}
+}
+
+server.listen(port, () => console.log(`log stream on ws://localhost:${port}`));
@@ -2,15 +2,69 @@ use plinth_util::{
     logging::log,
     time::{format_timestamp, now},
 };
-use winit::{dpi::PhysicalSize, window::Cursor};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    window::Cursor,
+};
+
+use crate::{
+    camera_animation::{Camera_Animation, Easing},
+    lanes::Lanes,
+};
 
 static ms_per_day: i64 = 1000 * 60 * 60 * 24;
 
+// How far the lane area can be zoomed vertically, 1.0 is the height lanes are laid out at
+const MIN_Y_SCALE: f64 = 0.25;
+const MAX_Y_SCALE: f64 = 8.0;
+
+// Positions on the gpu are f32 offsets from init_pos, which only have 24 bits of precision. Once the view is
+// further than this from init_pos (or REBASE_SCALES widths of the view, when zoomed out) it gets a new origin.
+const REBASE_DISTANCE: f64 = (1 << 20) as f64; // ~17 minutes, where f32 still resolves a tenth of a ms
+const REBASE_SCALES: f64 = 64.0;
+
+// Default zoom limits, as the amount of time across the whole screen
+const DEFAULT_MIN_VIEW_WIDTH: f64 = 1000.0; // 1 second
+const DEFAULT_MAX_VIEW_WIDTH: f64 = 50.0 * 365.25 * ms_per_day as f64; // 50 years
+
+// Panning past the bounds gets harder the further out the view is, this much of the view width out
+// halves how far the view moves
+const RUBBER_BAND_STIFFNESS: f64 = 0.5;
+// How long after the last pan the view waits before springing back inside the bounds, and how fast it does
+const SETTLE_DELAY_MS: f64 = 120.0;
+const SETTLE_TIME_CONSTANT_MS: f64 = 80.0;
+
+// Jumps and fits glide for this long
+const JUMP_ANIMATION_MS: f64 = 400.0;
+// Space left on both sides when fitting a range to the screen, in pixels
+const FIT_PADDING_PX: f64 = 40.0;
+// While following, now sits this far in from the right edge, in pixels
+const FOLLOW_MARGIN_PX: f64 = 24.0;
+
 pub struct Camera {
     pub init_pos: i64, // initial position, all positions passed to the gpu are relative to this. Just to avoid precision errors.
     pub pos: f64,      // timestamp, focused point in time on the timeline, center of screen
     pub scale: f64,    // width of the view in ms
     pub dimensions: PhysicalSize<u32>, // width and height of canvas/view in pixels
+    pub y_offset: f64, // pixels the lane area is scrolled down by
+    pub y_scale: f64,  // vertical zoom of the lane area, measured from the top of the canvas
+    pub min_scale: f64,
+    pub max_scale: f64,
+    pub bounds: Option<(f64, f64)>, // soft limits for pos, the view springs back inside them after panning
+    pub bounded: bool,              // whether bounds are enforced at all
+    last_pan: f64,                  // when the view was last panned or zoomed, in ms
+    pub animation: Option<Camera_Animation>, // glide in progress, any direct pan or zoom cancels it
+    pub following: bool, // keeps now pinned near the right edge, panning or jumping away stops it
+}
+
+// Snapshot of everything that affects what the camera sees, used to detect when view dependent data needs rebuilding.
+#[derive(Clone, Copy, PartialEq)]
+pub struct View {
+    pub pos: f64,
+    pub scale: f64,
+    pub dimensions: PhysicalSize<u32>,
+    pub y_offset: f64,
+    pub y_scale: f64,
 }
 
 impl Camera {
@@ -22,30 +76,333 @@ impl Camera {
             pos: now() - ms_per_day as f64 / 2.0,
             scale: ms_per_day as f64,
             dimensions: PhysicalSize::new(0, 0),
+            y_offset: 0.0,
+            y_scale: 1.0,
+            min_scale: DEFAULT_MIN_VIEW_WIDTH / 2.0,
+            max_scale: DEFAULT_MAX_VIEW_WIDTH / 2.0,
+            bounds: None,
+            bounded: true,
+            last_pan: 0.0,
+            animation: None,
+            following: false,
+        }
+    }
+
+    // Glides to a new view instead of jumping there. Replaces any animation already running, starting from
+    // wherever that one had got to.
+    pub fn animate_to(&mut self, pos: f64, scale: f64, duration: f64, easing: Easing) {
+        self.following = false;
+        self.animation = Some(Camera_Animation {
+            from_pos: self.pos,
+            from_scale: self.scale,
+            to_pos: pos,
+            to_scale: scale.clamp(self.min_scale, self.max_scale),
+            duration,
+            elapsed: 0.0,
+            easing,
+        });
+    }
+
+    // Glides to a new zoom level around the center of the view. Unlike the other glides it doesn't stop
+    // following, the view just keeps now at the right edge at the new zoom level.
+    pub fn zoom_to(&mut self, scale: f64, duration: f64, easing: Easing) {
+        let following = self.following;
+        let (pos, _) = self.target();
+        self.animate_to(pos, scale, duration, easing);
+        self.following = following;
+    }
+
+    // Glides over to now and keeps it in view from then on
+    pub fn follow(&mut self) {
+        let (_, scale) = self.target();
+        self.animate_to(
+            self.follow_pos(now(), scale),
+            scale,
+            JUMP_ANIMATION_MS,
+            Easing::EaseInOut,
+        );
+        self.following = true;
+    }
+
+    // Where pos has to be for a timestamp to sit just inside the right edge at a given scale
+    fn follow_pos(&self, timestamp: f64, scale: f64) -> f64 {
+        let width = self.dimensions.width as f64;
+        let margin = if width > FOLLOW_MARGIN_PX * 4.0 {
+            FOLLOW_MARGIN_PX / width * 2.0 * scale
+        } else {
+            0.0
+        };
+        timestamp - scale + margin
+    }
+
+    // Moves the view along with the current time while following, returns whether the view moved
+    pub fn keep_following(&mut self) -> bool {
+        if !self.following {
+            return false;
         }
+        let now = now();
+        // the bounds grow with time too, otherwise the view would spring back from now once following stops
+        if let Some((_, end)) = &mut self.bounds {
+            *end = end.max(now);
+        }
+        // a glide already underway, like a zoom, just has its destination kept up to date
+        if let Some(animation) = &self.animation {
+            let to_pos = self.follow_pos(now, animation.to_scale);
+            self.animation.as_mut().unwrap().to_pos = to_pos;
+            return false;
+        }
+        let pos = self.follow_pos(now, self.scale);
+        let moved = pos != self.pos;
+        self.pos = pos;
+        moved
+    }
+
+    // Jumps straight to a view, like one restored from a link
+    pub fn restore(&mut self, pos: f64, scale: f64) {
+        self.animation = None;
+        self.following = false;
+        self.pos = pos;
+        self.scale = scale.clamp(self.min_scale, self.max_scale);
+    }
+
+    // Glides to a timestamp at the current zoom level, stopping at the bounds
+    pub fn center_on(&mut self, timestamp: f64) {
+        let (_, scale) = self.target();
+        self.animate_to(
+            self.clamp_to_bounds(timestamp),
+            scale,
+            JUMP_ANIMATION_MS,
+            Easing::EaseInOut,
+        );
+    }
+
+    // Glides to show exactly start to end across the screen
+    pub fn show_range(&mut self, start: f64, end: f64) {
+        let (start, end) = (start.min(end), start.max(end));
+        self.animate_to(
+            self.clamp_to_bounds((start + end) / 2.0),
+            (end - start) / 2.0,
+            JUMP_ANIMATION_MS,
+            Easing::EaseInOut,
+        );
+    }
+
+    // Like show_range, but leaves some space at the edges so nothing sits right against them
+    pub fn fit_range(&mut self, start: f64, end: f64) {
+        let (start, end) = (start.min(end), start.max(end));
+        // a single moment has no length to fit
+        if end <= start {
+            self.center_on(start);
+            return;
+        }
+        let width = self.dimensions.width as f64;
+        let padding = if width > FIT_PADDING_PX * 4.0 {
+            (end - start) * FIT_PADDING_PX / (width - FIT_PADDING_PX * 2.0)
+        } else {
+            0.0
+        };
+        self.show_range(start - padding, end + padding);
     }
 
-    pub fn to_slice(&self) -> [f32; 4] {
+    fn clamp_to_bounds(&self, pos: f64) -> f64 {
+        match self.bounds {
+            Some((start, end)) if self.bounded => pos.clamp(start, end),
+            _ => pos,
+        }
+    }
+
+    // Where the view is headed, so repeated steps (like holding a key) build on each other instead of on
+    // wherever the animation happens to be
+    pub fn target(&self) -> (f64, f64) {
+        match &self.animation {
+            Some(animation) => (animation.to_pos, animation.to_scale),
+            None => (self.pos, self.scale),
+        }
+    }
+
+    // Advances the running animation by dt ms, returns whether the view moved
+    pub fn step_animation(&mut self, dt: f64) -> bool {
+        let Some(animation) = &mut self.animation else {
+            return false;
+        };
+        let (pos, scale) = animation.step(dt);
+        self.pos = pos;
+        self.scale = scale;
+        if animation.is_finished() {
+            self.animation = None;
+        }
+        true
+    }
+
+    // Limits for zooming, as the amount of time across the whole screen in ms
+    pub fn set_zoom_limits(&mut self, min_width: f64, max_width: f64) {
+        self.min_scale = min_width.max(1.0) / 2.0;
+        self.max_scale = (max_width / 2.0).max(self.min_scale);
+        self.scale = self.scale.clamp(self.min_scale, self.max_scale);
+    }
+
+    pub fn to_slice(&self) -> [f32; 8] {
         return [
-            (self.init_pos - self.pos as i64) as f32,
+            (self.pos - self.init_pos as f64) as f32,
             self.scale as f32,
-            0.0,
+            self.y_offset as f32,
+            self.y_scale as f32,
+            self.dimensions.width as f32,
+            self.dimensions.height as f32,
+            Lanes::visible_height(self) as f32,
             0.0,
         ];
     }
 
+    pub fn view(&self) -> View {
+        View {
+            pos: self.pos,
+            scale: self.scale,
+            dimensions: self.dimensions,
+            y_offset: self.y_offset,
+            y_scale: self.y_scale,
+        }
+    }
+
+    // Whether the view has drifted far enough from init_pos to lose precision on the gpu
+    pub fn needs_rebase(&self) -> bool {
+        (self.pos - self.init_pos as f64).abs() > REBASE_DISTANCE.max(self.scale * REBASE_SCALES)
+    }
+
+    // Moves init_pos to the center of the view. Everything positioned relative to the old one has to follow.
+    pub fn rebase(&mut self) {
+        self.init_pos = self.pos.round() as i64;
+    }
+
+    // The timestamps at the left and right edges of the screen
+    pub fn visible_range(&self) -> (f64, f64) {
+        (self.pos - self.scale, self.pos + self.scale)
+    }
+
+    // Converts a timestamp into normalized device coordinates, -1.0 is the left edge and 1.0 the right.
+    pub fn time_to_ndc(&self, timestamp: f64) -> f64 {
+        (timestamp - self.pos) / self.scale
+    }
+
+    // Converts a horizontal pixel position on the canvas into a timestamp.
+    pub fn screen_to_time(&self, x: f64) -> f64 {
+        (x / self.dimensions.width as f64 - 0.5) * 2.0 * self.scale + self.pos
+    }
+
+    // Converts a pixel position on the canvas into normalized device coordinates, y up.
+    pub fn screen_to_ndc(&self, position: PhysicalPosition<f64>) -> (f64, f64) {
+        (
+            position.x / self.dimensions.width as f64 * 2.0 - 1.0,
+            1.0 - position.y / self.dimensions.height as f64 * 2.0,
+        )
+    }
+
+    // How many ms a span of pixels covers at the current zoom level.
+    pub fn px_to_ms(&self, px: f64) -> f64 {
+        px / self.dimensions.width as f64 * 2.0 * self.scale
+    }
+
+    // How many pixels a span of ms takes up on screen at the current zoom level.
+    pub fn ms_to_px(&self, ms: f64) -> f64 {
+        ms / (2.0 * self.scale) * self.dimensions.width as f64
+    }
+
     pub fn zoom(&mut self, delta: f64, cursor_x: f64) {
-        let cursor_pos =
-            (cursor_x / self.dimensions.width as f64 - 0.5) * -2.0 * self.scale + self.pos;
+        self.zoom_by((1.1 as f64).powf(-delta / 120.0), cursor_x);
+    }
+
+    // Multiplies the scale by factor, keeping the time under cursor_x where it is
+    pub fn zoom_by(&mut self, factor: f64, cursor_x: f64) {
+        let cursor_pos = self.screen_to_time(cursor_x);
         let cursor_delta = self.pos - cursor_pos;
-        let scalar = (1.1 as f64).powf(-delta / 120.0);
-        self.scale = self.scale * scalar;
+        let new_scale = (self.scale * factor).clamp(self.min_scale, self.max_scale);
+        // the scalar that actually applied after clamping, so the time under the cursor stays put
+        let scalar = new_scale / self.scale;
+        self.scale = new_scale;
         self.pos = cursor_pos + cursor_delta * scalar;
+        self.last_pan = now();
+        self.animation = None;
     }
 
     pub fn pan(&mut self, dx: f64) {
         let screen_width = self.dimensions.width as f64; // need to figure out how to get this dynamically
-        self.pos += ((dx / screen_width) * self.scale as f64 * 2.0);
+        let mut delta = -((dx / screen_width) * self.scale as f64 * 2.0);
+        // past the bounds, moving further out meets more and more resistance
+        let overshoot = self.overshoot();
+        if overshoot != 0.0 && overshoot.signum() == delta.signum() {
+            delta /= 1.0 + overshoot.abs() / (self.scale * 2.0 * RUBBER_BAND_STIFFNESS);
+        }
+        self.pos += delta;
+        self.last_pan = now();
+        self.animation = None;
+        self.following = false;
         // log(format_timestamp(self.init_pos + self.pos as i64).as_str());
     }
+
+    // How far pos is outside the bounds, negative when before the start
+    pub fn overshoot(&self) -> f64 {
+        match self.bounds {
+            Some((start, end)) if self.bounded => {
+                if self.pos < start {
+                    self.pos - start
+                } else if self.pos > end {
+                    self.pos - end
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        }
+    }
+
+    // Springs the view back inside the bounds once panning has stopped. held is whether something (like a
+    // drag) is still holding the view where it is. Returns whether the view moved.
+    pub fn settle(&mut self, dt: f64, held: bool) -> bool {
+        let overshoot = self.overshoot();
+        if overshoot == 0.0
+            || held
+            || self.following
+            || self.animation.is_some()
+            || now() - self.last_pan < SETTLE_DELAY_MS
+        {
+            return false;
+        }
+        let remaining = overshoot * (-dt / SETTLE_TIME_CONSTANT_MS).exp();
+        // snap the last bit, it would take forever to get there exponentially
+        if self.ms_to_px(remaining.abs()) < 0.5 {
+            self.pos -= overshoot;
+        } else {
+            self.pos -= overshoot - remaining;
+        }
+        true
+    }
+
+    // Converts a vertical pixel position on the canvas into a position in the unscrolled, unzoomed lane layout.
+    pub fn screen_to_content_y(&self, y: f64) -> f64 {
+        (y + self.y_offset) / self.y_scale
+    }
+
+    // Converts a position in the lane layout into a vertical pixel position on the canvas.
+    pub fn content_to_screen_y(&self, y: f64) -> f64 {
+        y * self.y_scale - self.y_offset
+    }
+
+    pub fn pan_y(&mut self, dy: f64) {
+        self.y_offset -= dy;
+    }
+
+    // Zooms the lane area around the cursor, so the lane under it stays put
+    pub fn zoom_y(&mut self, delta: f64, cursor_y: f64) {
+        let cursor_content = self.screen_to_content_y(cursor_y);
+        let scalar = (1.1 as f64).powf(delta / 120.0);
+        self.y_scale = (self.y_scale * scalar).clamp(MIN_Y_SCALE, MAX_Y_SCALE);
+        self.y_offset = cursor_content * self.y_scale - cursor_y;
+    }
+
+    // Keeps the lane area from scrolling past its ends. content_height is the bottom of the lane layout,
+    // visible_height how much of the canvas it gets.
+    pub fn clamp_y(&mut self, content_height: f64, visible_height: f64) {
+        let max_offset = (content_height * self.y_scale - visible_height).max(0.0);
+        self.y_offset = self.y_offset.clamp(0.0, max_offset);
+    }
 }
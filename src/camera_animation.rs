@@ -0,0 +1,56 @@
+// How an animation's progress is spread over its duration
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    EaseOut, // starts fast and slows into the target, good for responding to input
+    EaseInOut,
+}
+
+impl Easing {
+    // Maps linear progress from 0 to 1 onto eased progress from 0 to 1
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+// A glide from one view to another. Scale is interpolated on a log scale so zooming feels like the same speed
+// whether it's going from seconds to minutes or from months to years.
+pub struct Camera_Animation {
+    pub from_pos: f64,
+    pub from_scale: f64,
+    pub to_pos: f64,
+    pub to_scale: f64,
+    pub duration: f64, // ms
+    pub elapsed: f64,  // ms
+    pub easing: Easing,
+}
+
+impl Camera_Animation {
+    // Moves the animation forward by dt ms and returns the pos and scale for that point in it
+    pub fn step(&mut self, dt: f64) -> (f64, f64) {
+        self.elapsed += dt;
+        let t = if self.duration > 0.0 {
+            self.easing.apply(self.elapsed / self.duration)
+        } else {
+            1.0
+        };
+        let pos = self.from_pos + (self.to_pos - self.from_pos) * t;
+        let scale = self.from_scale * (self.to_scale / self.from_scale).powf(t);
+        (pos, scale)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
@@ -3,18 +3,34 @@ use std::collections::VecDeque;
 use mosaic_model::log::Log;
 
 use crate::camera::Camera;
+use crate::density::Density;
 use crate::gpu_data::GPU_Data;
+use crate::lanes::Lanes;
+use crate::log_store::{Log_Key, Log_Store};
+use crate::sessions::Sessions;
 
 pub struct Data {
-    pub logs: Vec<Log>,
+    pub logs: Log_Store,
     pub queue: VecDeque<GPU_Data>,
+    pub sessions: Sessions, // paired from the start and stop logs, updated whenever logs arrive
+    pub overlay: Option<Vec<GPU_Data>>, // view dependent rects, replaces the previous overlay wholesale when set
+    pub lanes: Lanes,
+    pub lane_layout: Option<Vec<f32>>, // new lane positions for the gpu, set when the layout changes
+    pub density: Density,
+    pub density_active: bool, // whether the overlay is showing the density in place of the logs
 }
 
 impl Data {
     pub fn new() -> Self {
         Self {
-            logs: vec![],
+            logs: Log_Store::new(),
             queue: VecDeque::new(),
+            sessions: Sessions::new(),
+            overlay: None,
+            lanes: Lanes::new(),
+            lane_layout: None,
+            density: Density::new(),
+            density_active: false,
         }
     }
 }
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    camera::Camera,
+    gpu_data::{span_geometry, GPU_Data, DENSITY_COLOR, LOG_RECT_HEIGHT},
+    lanes::Lanes,
+};
+
+static ms_per_minute: i64 = 1000 * 60;
+static ms_per_hour: i64 = ms_per_minute * 60;
+static ms_per_day: i64 = ms_per_hour * 24;
+
+// Bin sizes we keep counts for, from finest to coarsest
+static LEVELS: [i64; 7] = [
+    ms_per_minute,
+    10 * ms_per_minute,
+    ms_per_hour,
+    6 * ms_per_hour,
+    ms_per_day,
+    7 * ms_per_day,
+    30 * ms_per_day,
+];
+
+// Once a pixel covers more time than this, individual logs are just noise and the density is drawn instead
+const MAX_DETAIL_MS_PER_PX: f64 = 60_000.0;
+// Bins narrower than this (in pixels) are merged into a coarser level
+const MIN_BIN_WIDTH: f64 = 4.0;
+
+// Number of logs per lane in fixed size time bins
+struct Density_Level {
+    bin_ms: i64,
+    bins: BTreeMap<(usize, i64), u32>, // (lane, bin index) -> count
+}
+
+// Log counts over time at several resolutions, kept up to date as logs arrive so zoomed out views don't have to
+// touch every log.
+pub struct Density {
+    levels: Vec<Density_Level>,
+}
+
+impl Density {
+    pub fn new() -> Self {
+        Self {
+            levels: LEVELS
+                .iter()
+                .map(|bin_ms| Density_Level {
+                    bin_ms: *bin_ms,
+                    bins: BTreeMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn insert(&mut self, lane: usize, timestamp: i64) {
+        for level in &mut self.levels {
+            *level
+                .bins
+                .entry((lane, timestamp.div_euclid(level.bin_ms)))
+                .or_insert(0) += 1;
+        }
+    }
+
+    // Whether the camera is zoomed out far enough to draw the density instead of the logs themselves
+    pub fn is_active(camera: &Camera) -> bool {
+        camera.dimensions.width > 0 && camera.px_to_ms(1.0) > MAX_DETAIL_MS_PER_PX
+    }
+
+    // The finest level with bins wide enough to see
+    fn level(&self, camera: &Camera) -> &Density_Level {
+        let min_bin_ms = camera.px_to_ms(MIN_BIN_WIDTH);
+        self.levels
+            .iter()
+            .find(|level| level.bin_ms as f64 >= min_bin_ms)
+            .unwrap_or(self.levels.last().unwrap())
+    }
+
+    // One bar per non empty bin on screen, in each expanded lane. Bar heights are relative to the busiest bin
+    // in view, on a log scale so quiet stretches don't disappear next to busy ones.
+    pub fn gpu_data(&self, lanes: &Lanes, camera: &Camera) -> Vec<GPU_Data> {
+        let mut data = vec![];
+        let level = self.level(camera);
+        let (start, end) = camera.visible_range();
+        let (first_bin, last_bin) = (
+            (start as i64).div_euclid(level.bin_ms),
+            (end as i64).div_euclid(level.bin_ms),
+        );
+
+        let visible: Vec<(usize, i64, u32)> = lanes
+            .order
+            .iter()
+            .filter(|id| !lanes.lanes[**id].collapsed)
+            .flat_map(|id| {
+                level
+                    .bins
+                    .range((*id, first_bin)..=(*id, last_bin))
+                    .map(|((lane, bin), count)| (*lane, *bin, *count))
+            })
+            .collect();
+        let max = visible
+            .iter()
+            .map(|(_, _, count)| *count)
+            .max()
+            .unwrap_or(0);
+        if max == 0 {
+            return data;
+        }
+
+        let max_log = (1.0 + max as f32).ln();
+        for (lane, bin, count) in visible {
+            let (x, y, w) =
+                span_geometry(bin * level.bin_ms, (bin + 1) * level.bin_ms, lane, camera);
+            data.push(GPU_Data::Rect {
+                x,
+                y,
+                w,
+                h: LOG_RECT_HEIGHT * (1.0 + count as f32).ln() / max_log,
+                color_index: DENSITY_COLOR,
+                fixed: 0.0,
+            });
+        }
+        data
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use plinth_util::logging::log;
+
+pub const ATLAS_SIZE: u32 = 1024;
+// Empty space left around each glyph so linear filtering doesn't bleed neighbours into each other
+const PADDING: u32 = 1;
+
+static FONT_BYTES: &[u8] = include_bytes!("fonts/DejaVuSansMono.ttf");
+
+// Where a glyph lives in the atlas and how to place it relative to the pen, all in pixels.
+#[derive(Clone, Copy)]
+pub struct Glyph_Info {
+    pub atlas_x: u32,
+    pub atlas_y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub min_x: f32, // offset of the bitmap's left edge from the pen
+    pub min_y: f32, // offset of the bitmap's top edge from the baseline, y down
+}
+
+// A freshly rasterized glyph waiting to be copied into the atlas texture
+pub struct Glyph_Upload {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+// CPU side of the text renderer. Rasterizes glyphs on demand, packs them into rows of a single
+// channel texture, and turns strings into glyph instances for the text pipeline.
+pub struct Glyph_Atlas {
+    font: FontRef<'static>,
+    glyphs: HashMap<(char, u32), Option<Glyph_Info>>, // None for glyphs with no outline (spaces) or that didn't fit
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+    full: bool, // set the first time a glyph doesn't fit, so that's only logged once
+    pub pending: Vec<Glyph_Upload>,
+}
+
+impl Glyph_Atlas {
+    pub fn new() -> Self {
+        Self {
+            font: FontRef::try_from_slice(FONT_BYTES).expect("Failed to load the bundled font."),
+            glyphs: HashMap::new(),
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+            full: false,
+            pending: vec![],
+        }
+    }
+
+    // Look up a glyph, rasterizing it into the atlas the first time it is seen at this size
+    fn glyph(&mut self, c: char, size: u32) -> Option<Glyph_Info> {
+        if let Some(info) = self.glyphs.get(&(c, size)) {
+            return *info;
+        }
+
+        let glyph = self
+            .font
+            .glyph_id(c)
+            .with_scale_and_position(PxScale::from(size as f32), point(0.0, 0.0));
+        let info = match self.font.outline_glyph(glyph) {
+            Some(outline) => {
+                let bounds = outline.px_bounds();
+                let width = bounds.width() as u32;
+                let height = bounds.height() as u32;
+
+                match self.allocate(width, height) {
+                    Some((x, y)) => {
+                        let mut pixels = vec![0; (width * height) as usize];
+                        outline.draw(|px, py, coverage| {
+                            if px < width && py < height {
+                                pixels[(py * width + px) as usize] = (coverage * 255.0) as u8;
+                            }
+                        });
+                        self.pending.push(Glyph_Upload {
+                            x,
+                            y,
+                            width,
+                            height,
+                            pixels,
+                        });
+
+                        Some(Glyph_Info {
+                            atlas_x: x,
+                            atlas_y: y,
+                            width,
+                            height,
+                            min_x: bounds.min.x,
+                            min_y: bounds.min.y,
+                        })
+                    }
+                    // the atlas is full, the glyph is skipped and the rest of the text still draws
+                    None => {
+                        if !self.full {
+                            log("Glyph atlas is full, glyphs that don't fit will be skipped");
+                            self.full = true;
+                        }
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        self.glyphs.insert((c, size), info);
+        info
+    }
+
+    // Shelf packing, glyphs fill a row left to right and a new row starts below the tallest glyph so far.
+    // Nothing is ever evicted, once a glyph doesn't fit this returns None and leaves the rows as they were.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width + PADDING > ATLAS_SIZE || height + PADDING > ATLAS_SIZE {
+            return None;
+        }
+
+        let (mut x, mut y, mut row_height) = (self.cursor_x, self.cursor_y, self.row_height);
+        if x + width + PADDING > ATLAS_SIZE {
+            x = 0;
+            y += row_height + PADDING;
+            row_height = 0;
+        }
+        if y + height + PADDING > ATLAS_SIZE {
+            return None;
+        }
+
+        self.cursor_x = x + width + PADDING;
+        self.cursor_y = y;
+        self.row_height = row_height.max(height);
+        Some((x, y))
+    }
+
+    // Width of a string in pixels at the given size
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        let scaled = self.font.as_scaled(PxScale::from(size.round()));
+        let mut width = 0.0;
+        let mut previous = None;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                width += scaled.kern(previous, id);
+            }
+            width += scaled.h_advance(id);
+            previous = Some(id);
+        }
+        width
+    }
+
+    // Lays out a single line of text as glyph instances. x and y locate the baseline and follow the same rules as
+    // GPU_Data::Rect, align shifts the line left by that fraction of its width (0.5 centers it on x).
+    pub fn layout(
+        &mut self,
+        text: &str,
+        x: f32,
+        y: f32,
+        size: f32,
+        align: f32,
+        color_index: f32,
+        fixed: f32,
+    ) -> Vec<f32> {
+        let size = size.round().max(1.0) as u32;
+        let mut instances = vec![];
+        let mut pen = -self.measure(text, size as f32) * align;
+
+        // FontRef is just a reference to the bundled bytes, cloning it lets glyph() borrow self mutably below
+        let font = self.font.clone();
+        let scaled = font.as_scaled(PxScale::from(size as f32));
+        let mut previous = None;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                pen += scaled.kern(previous, id);
+            }
+            previous = Some(id);
+
+            if let Some(info) = self.glyph(c, size) {
+                let (w, h) = (info.width as f32, info.height as f32);
+                // offset of the quad's center from the baseline origin, in y up pixels
+                let offset_x = (pen + info.min_x + w / 2.0).round();
+                let offset_y = -(info.min_y + h / 2.0).round();
+                let atlas_size = ATLAS_SIZE as f32;
+
+                instances.extend_from_slice(&[
+                    x,
+                    y,
+                    offset_x,
+                    offset_y,
+                    w,
+                    h,
+                    info.atlas_x as f32 / atlas_size,
+                    info.atlas_y as f32 / atlas_size,
+                    w / atlas_size,
+                    h / atlas_size,
+                    color_index,
+                    fixed,
+                ]);
+            }
+            pen += scaled.h_advance(id);
+        }
+
+        instances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_packs_rows() {
+        let mut atlas = Glyph_Atlas::new();
+        let side = ATLAS_SIZE / 2 - PADDING;
+        assert_eq!(atlas.allocate(side, side), Some((0, 0)));
+        assert_eq!(atlas.allocate(side, 10), Some((side + PADDING, 0)));
+        assert_eq!(atlas.allocate(side, 10), Some((0, side + PADDING)));
+    }
+
+    #[test]
+    fn full_atlas_skips_glyphs() {
+        let mut atlas = Glyph_Atlas::new();
+        assert_eq!(atlas.allocate(10, 10), Some((0, 0)));
+        // too big to ever fit, and it doesn't move the next glyph to a new row
+        assert_eq!(atlas.allocate(ATLAS_SIZE, 10), None);
+        assert_eq!(atlas.allocate(10, 10), Some((10 + PADDING, 0)));
+
+        let side = ATLAS_SIZE / 2 - PADDING;
+        assert_eq!(atlas.allocate(side, side), Some((20 + 2 * PADDING, 0)));
+        assert_eq!(atlas.allocate(side, side), Some((0, side + PADDING)));
+        let y = side + PADDING;
+        assert_eq!(atlas.allocate(10, 10), Some((side + PADDING, y)));
+        // there's no row below the last one, but what's left of it still takes glyphs that fit
+        assert_eq!(atlas.allocate(side, side), None);
+        assert_eq!(atlas.allocate(side, side), None);
+        assert_eq!(atlas.allocate(10, 10), Some((side + 10 + 2 * PADDING, y)));
+
+        // once nothing fits, text lays out without the glyphs that are missing
+        while atlas.allocate(1, 1).is_some() {}
+        let pending = atlas.pending.len();
+        assert!(atlas
+            .layout("xyz", 0.0, 0.0, 12.0, 0.0, 0.0, 1.0)
+            .is_empty());
+        assert_eq!(atlas.pending.len(), pending);
+    }
+}
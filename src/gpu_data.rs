@@ -1,7 +1,24 @@
 use crate::camera::Camera;
 use mosaic_model::log::Log;
 
+// Indices into the color palette defined in PlinthRenderer::create_pipeline
+pub const TIMELINE_COLOR: f32 = 0.0;
+pub const LOG_COLOR: f32 = 1.0;
+pub const GRID_COLOR: f32 = 2.0;
+pub const TICK_COLOR: f32 = 3.0;
+pub const HIGHLIGHT_COLOR: f32 = 4.0;
+pub const TOOLTIP_COLOR: f32 = 5.0;
+pub const SELECTION_COLOR: f32 = 6.0;
+pub const DENSITY_COLOR: f32 = 7.0;
+pub const NOW_COLOR: f32 = 8.0;
+
+// Share of its lane's height that a log rect fills
+pub const LOG_RECT_HEIGHT: f32 = 0.6;
+
 pub enum GPU_Data {
+    // x and y are the center of the rect. When fixed, everything is in screen space. Otherwise x is ms relative to
+    // Camera::init_pos and w is a duration in ms, y is the id of the lane the rect sits in and h is the share of
+    // that lane's height it fills.
     Rect {
         x: f32,
         y: f32,
@@ -10,23 +27,89 @@ pub enum GPU_Data {
         color_index: f32,
         fixed: f32,
     },
+    // Same as Rect, but only a border thickness pixels wide is drawn
+    Outline {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color_index: f32,
+        fixed: f32,
+        thickness: f32,
+    },
+    // A log or session on the timeline, from start to end in the given lane. Kept as timestamps until it is
+    // uploaded, so its position stays exact no matter how far it is from Camera::init_pos.
+    Span {
+        start: i64,
+        end: i64,
+        lane: usize,
+    },
+    // Takes a Span that was queued before back out of the rect buffer
+    RemoveSpan {
+        start: i64,
+        end: i64,
+        lane: usize,
+    },
     Color {
         r: f32,
         g: f32,
         b: f32,
         a: f32,
     },
+    // A single line of text, x and y place the left end of the baseline (or its center if align is 0.5).
+    // size is in pixels.
+    Text {
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        align: f32,
+        color_index: f32,
+        fixed: f32,
+    },
+    // Takes a line of Text that was queued before back out of the text buffer, given the same way it was queued
+    RemoveText {
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        align: f32,
+        color_index: f32,
+        fixed: f32,
+    },
 }
 
 impl GPU_Data {
-    pub fn from_log(log: &Log, camera: &Camera) -> Self {
+    // A single moment in time, the shader widens it to a minimum width so it stays visible
+    pub fn from_log(log: &Log, lane: usize) -> Self {
+        Self::Span {
+            start: log.timestamp,
+            end: log.timestamp,
+            lane,
+        }
+    }
+
+    // Something that lasted from start to end, as wide as its real duration on the timeline. Only precise near
+    // the camera, anything that stays in the rect buffer should be a Span.
+    pub fn from_span(start: i64, end: i64, lane: usize, camera: &Camera) -> Self {
+        let (x, y, w) = span_geometry(start, end, lane, camera);
         Self::Rect {
-            x: (log.timestamp - camera.init_pos) as f32,
-            y: 0.0,
-            w: 0.001,
-            h: 0.04,
-            color_index: 1.0,
+            x,
+            y,
+            w,
+            h: LOG_RECT_HEIGHT,
+            color_index: LOG_COLOR,
             fixed: 0.0,
         }
     }
 }
+
+// x, y and width of a rect covering start to end in a lane, for building rects that aren't plain logs
+pub fn span_geometry(start: i64, end: i64, lane: usize, camera: &Camera) -> (f32, f32, f32) {
+    (
+        // center the rect on the span, offsets are computed in i64 so they stay exact
+        ((start - camera.init_pos) as f64 + (end - start) as f64 / 2.0) as f32,
+        lane as f32,
+        (end - start) as f32,
+    )
+}
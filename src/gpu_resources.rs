@@ -1,23 +1,47 @@
 use std::borrow::Cow;
+use std::ops::Range;
 
 use plinth_core::graphics::Graphics;
 use plinth_util::logging::log;
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, Buffer, BufferBindingType, BufferDescriptor,
-    BufferUsages, Color, CommandEncoderDescriptor, FragmentState, LoadOp, Operations,
-    PipelineLayoutDescriptor, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
-    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp,
-    TextureViewDescriptor, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+    BindGroupLayoutEntry, BindingResource, BlendState, Buffer, BufferBindingType, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor, Extent3d,
+    FilterMode, FragmentState, LoadOp, Operations, Origin3d, PipelineLayoutDescriptor,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StoreOp, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
 };
 
-use crate::{camera::Camera, gpu_data::GPU_Data};
+use crate::{
+    camera::Camera,
+    glyph_atlas::{Glyph_Atlas, ATLAS_SIZE},
+    gpu_data::{GPU_Data, LOG_COLOR, LOG_RECT_HEIGHT},
+    lanes::{LANE_FLOATS, MAX_LANES},
+    sorted_rects::Sorted_Rects,
+};
+
+pub const RECT_FLOATS: usize = 7; // x, y, w, h, color_index, fixed, outline
+                                  // Must match MIN_WIDTH_PX in rect_shader.wgsl
+const MIN_WIDTH_PX: f64 = 2.0;
+const GLYPH_FLOATS: usize = 12; // x, y, offset_x, offset_y, w, h, u, v, uv_w, uv_h, color_index, fixed
+
+// A line of text in the text buffer, kept so the buffer can be rebuilt when lines are removed or the origin moves
+struct Queued_Text {
+    text: String,
+    anchor: f64,      // timestamp at x, or x itself when the text is fixed
+    style: [f32; 5],  // y, size, align, color_index, fixed
+    glyphs: Vec<f32>, // GLYPH_FLOATS per glyph, same layout as the gpu buffer
+}
 
 // This is where we store and initialize all of the freaky-ahh wgpu resources that the render pipeline needs to run.
 pub struct GPU_Resources {
     pub rect_shader: Option<wgpu::ShaderModule>,
     pub rect_buffer: Option<wgpu::Buffer>,
     pub rect_count: Option<u32>,
+    pub sorted_rects: Sorted_Rects, // what's in rect_buffer, in time order
     pub index_buffer: Option<wgpu::Buffer>,
     pub index_count: Option<u32>,
     pub color_buffer: Option<wgpu::Buffer>,
@@ -29,6 +53,21 @@ pub struct GPU_Resources {
     pub combined_bind_group: Option<wgpu::BindGroup>,
     pub color_bind_group: Option<wgpu::BindGroup>,
     pub rect_pipeline_layout: Option<wgpu::PipelineLayout>,
+    pub overlay_buffer: Option<wgpu::Buffer>,
+    pub overlay_count: Option<u32>,
+    pub glyph_atlas: Glyph_Atlas,
+    pub text_shader: Option<wgpu::ShaderModule>,
+    pub atlas_texture: Option<wgpu::Texture>,
+    pub atlas_bind_group_layout: Option<wgpu::BindGroupLayout>,
+    pub atlas_bind_group: Option<wgpu::BindGroup>,
+    pub text_pipeline_layout: Option<wgpu::PipelineLayout>,
+    pub text_pipeline: Option<wgpu::RenderPipeline>,
+    pub text_buffer: Option<wgpu::Buffer>,
+    pub text_count: Option<u32>,
+    queued_text: Vec<Queued_Text>, // what's in text_buffer, in the order it was queued
+    pub overlay_text_buffer: Option<wgpu::Buffer>,
+    pub overlay_text_count: Option<u32>,
+    pub lane_buffer: Option<wgpu::Buffer>,
 }
 
 impl GPU_Resources {
@@ -37,6 +76,7 @@ impl GPU_Resources {
             rect_shader: None,
             rect_buffer: None,
             rect_count: None,
+            sorted_rects: Sorted_Rects::new(0),
             index_buffer: None,
             index_count: None,
             color_buffer: None,
@@ -48,6 +88,21 @@ impl GPU_Resources {
             combined_bind_group_layout: None,
             combined_bind_group: None,
             rect_pipeline_layout: None,
+            overlay_buffer: None,
+            overlay_count: None,
+            glyph_atlas: Glyph_Atlas::new(),
+            text_shader: None,
+            atlas_texture: None,
+            atlas_bind_group_layout: None,
+            atlas_bind_group: None,
+            text_pipeline_layout: None,
+            text_pipeline: None,
+            text_buffer: None,
+            text_count: None,
+            queued_text: vec![],
+            overlay_text_buffer: None,
+            overlay_text_count: None,
+            lane_buffer: None,
         }
     }
 
@@ -102,7 +157,7 @@ impl GPU_Resources {
         rect_buffer.unmap(); // Unmap after writing
 
         // Store the GPU buffer in self
-        self.rect_count = Some((rect_data.len() / 6) as u32); // Each rectangle has 6 values
+        self.rect_count = Some((rect_data.len() / RECT_FLOATS) as u32); // Each rectangle has 7 values
         self.rect_buffer = Some(rect_buffer);
     }
 
@@ -164,7 +219,7 @@ impl GPU_Resources {
         // Create the camera uniform buffer
         let camera_buffer = device.create_buffer(&BufferDescriptor {
             label: Some("Camera Uniform Buffer"),
-            size: std::mem::size_of::<[f32; 4]>() as u64,
+            size: std::mem::size_of::<[f32; 8]>() as u64,
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             mapped_at_creation: true,
         });
@@ -172,7 +227,7 @@ impl GPU_Resources {
         // Write the camera data to the buffer
         {
             let mut buffer_view = camera_buffer.slice(..).get_mapped_range_mut();
-            bytemuck::cast_slice_mut::<u8, [f32; 4]>(&mut buffer_view)
+            bytemuck::cast_slice_mut::<u8, [f32; 8]>(&mut buffer_view)
                 .copy_from_slice(&[camera_data]);
         }
         camera_buffer.unmap(); // Unmap after writing
@@ -224,6 +279,9 @@ impl GPU_Resources {
         if self.color_bind_group_layout.is_none() || self.camera_bind_group_layout.is_none() {
             panic!("Color and camera bind group layouts must be initialized before combined bind group");
         }
+        if self.lane_buffer.is_none() {
+            panic!("Lane buffer must be initialized before combined bind group");
+        }
 
         let device = &gfx.device;
 
@@ -254,6 +312,17 @@ impl GPU_Resources {
                         },
                         count: None,
                     },
+                    // Lane layout binding (group 0, binding 2)
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -272,6 +341,11 @@ impl GPU_Resources {
                     binding: 1,
                     resource: self.camera_buffer.as_ref().unwrap().as_entire_binding(),
                 },
+                // Lane layout binding
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.lane_buffer.as_ref().unwrap().as_entire_binding(),
+                },
             ],
         });
 
@@ -280,6 +354,24 @@ impl GPU_Resources {
         self.combined_bind_group = Some(combined_bind_group);
     }
 
+    // Sized for MAX_LANES up front, so the combined bind group can keep pointing at the same buffer
+    pub fn init_lane_buffer(&mut self, gfx: &mut Graphics) {
+        self.lane_buffer = Some(gfx.device.create_buffer(&BufferDescriptor {
+            label: Some("Lane Buffer"),
+            size: (MAX_LANES * LANE_FLOATS * std::mem::size_of::<f32>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    }
+
+    pub fn write_lanes(&mut self, layout: Vec<f32>, gfx: &mut Graphics) {
+        if let Some(lane_buffer) = &self.lane_buffer {
+            let floats = layout.len().min(MAX_LANES * LANE_FLOATS);
+            gfx.queue
+                .write_buffer(lane_buffer, 0, bytemuck::cast_slice(&layout[..floats]));
+        }
+    }
+
     pub fn init_rect_pipeline_layout(&mut self, gfx: &mut Graphics) {
         // Make sure combined bind group layout is initialized
         if self.combined_bind_group_layout.is_none() {
@@ -301,7 +393,7 @@ impl GPU_Resources {
 
         // Define the vertex buffer layout
         let rect_buffer_layout = VertexBufferLayout {
-            array_stride: 6 * std::mem::size_of::<f32>() as u64, // x, y, w, h, color_index, fixed
+            array_stride: RECT_FLOATS as u64 * std::mem::size_of::<f32>() as u64, // x, y, w, h, color_index, fixed, outline
             step_mode: VertexStepMode::Instance, // Use instance mode to draw multiple rectangles
             attributes: &[
                 // Position (x, y)
@@ -328,6 +420,12 @@ impl GPU_Resources {
                     offset: 5 * std::mem::size_of::<f32>() as u64,
                     shader_location: 3,
                 },
+                // Outline thickness in pixels, 0 for a filled rect
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 6 * std::mem::size_of::<f32>() as u64,
+                    shader_location: 4,
+                },
             ],
         };
 
@@ -361,7 +459,10 @@ impl GPU_Resources {
         gfx: &mut plinth_core::graphics::Graphics,
     ) {
         let mut rects = vec![];
+        let mut removed_rects = vec![];
         let mut colors = vec![];
+        let mut texts = vec![];
+        let mut removed_texts = vec![];
         while !queue.is_empty() {
             match queue.pop_front().unwrap() {
                 GPU_Data::Rect {
@@ -372,80 +473,229 @@ impl GPU_Resources {
                     color_index,
                     fixed,
                 } => {
-                    rects.extend_from_slice(&[x, y, w, h, color_index, fixed]);
+                    let center = self.sorted_rects.origin as f64 + x as f64;
+                    rects.push(([x, y, w, h, color_index, fixed, 0.0], center));
+                }
+                GPU_Data::Outline {
+                    x,
+                    y,
+                    w,
+                    h,
+                    color_index,
+                    fixed,
+                    thickness,
+                } => {
+                    let center = self.sorted_rects.origin as f64 + x as f64;
+                    rects.push(([x, y, w, h, color_index, fixed, thickness], center));
+                }
+                GPU_Data::Span { start, end, lane } => {
+                    rects.push(span_instance(start, end, lane, self.sorted_rects.origin));
+                }
+                GPU_Data::RemoveSpan { start, end, lane } => {
+                    removed_rects.push(span_instance(start, end, lane, self.sorted_rects.origin));
                 }
                 GPU_Data::Color { r, g, b, a } => {
                     colors.extend_from_slice(&[r, g, b, a]);
                 }
+                GPU_Data::Text {
+                    text,
+                    x,
+                    y,
+                    size,
+                    align,
+                    color_index,
+                    fixed,
+                } => {
+                    let glyphs =
+                        self.glyph_atlas
+                            .layout(&text, x, y, size, align, color_index, fixed);
+                    texts.push(Queued_Text {
+                        anchor: self.text_anchor(x, fixed),
+                        text,
+                        style: [y, size, align, color_index, fixed],
+                        glyphs,
+                    });
+                }
+                GPU_Data::RemoveText {
+                    text,
+                    x,
+                    y,
+                    size,
+                    align,
+                    color_index,
+                    fixed,
+                } => {
+                    removed_texts.push((
+                        self.text_anchor(x, fixed),
+                        text,
+                        [y, size, align, color_index, fixed],
+                    ));
+                }
             }
         }
-        if !rects.is_empty() {
-            self.append_rects(rects, gfx);
+        if !rects.is_empty() || !removed_rects.is_empty() {
+            self.update_rects(rects, removed_rects, gfx);
+        }
+        if !texts.is_empty() || !removed_texts.is_empty() {
+            self.update_text(texts, removed_texts, gfx);
         }
         if !colors.is_empty() {
             self.append_colors(colors, gfx);
         }
     }
 
-    fn append_rects(&mut self, rects: Vec<f32>, gfx: &mut plinth_core::graphics::Graphics) {
-        // If we don't have a rect buffer yet, we can't append
+    // Rects are kept ordered by time so render can draw just the ones on screen. New rects usually belong at the
+    // end, so usually only they get written. Removing a rect rewrites everything after it.
+    fn update_rects(
+        &mut self,
+        rects: Vec<([f32; RECT_FLOATS], f64)>,
+        removed_rects: Vec<([f32; RECT_FLOATS], f64)>,
+        gfx: &mut plinth_core::graphics::Graphics,
+    ) {
+        let first_removed = self.sorted_rects.remove(removed_rects);
+        let first_changed = self.sorted_rects.insert(rects).min(first_removed);
+        let instances = &self.sorted_rects.instances;
+
+        // If we don't have a rect buffer yet, make one with everything in it
         if self.rect_buffer.is_none() {
-            self.init_rect_buffer(rects, gfx);
+            self.init_rect_buffer(instances.clone(), gfx);
             return;
         }
 
-        // Get the current rect count
-        let current_rect_count = self.rect_count.unwrap_or(0);
+        let needed_size = (instances.len() * std::mem::size_of::<f32>()) as u64;
+        if needed_size > self.rect_buffer.as_ref().unwrap().size() {
+            // Need to create a larger buffer, everything gets rewritten anyway
+            let new_buffer = gfx.device.create_buffer(&BufferDescriptor {
+                label: Some("Rectangle Buffer (Resized)"),
+                // Allocate some extra space to avoid frequent resizing
+                size: needed_size * 2,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            gfx.queue
+                .write_buffer(&new_buffer, 0, bytemuck::cast_slice(instances));
+            self.rect_buffer = Some(new_buffer);
+        } else {
+            // Buffer is large enough, just rewrite from the first rect that moved
+            let offset = (first_changed * RECT_FLOATS * std::mem::size_of::<f32>()) as u64;
+            gfx.queue.write_buffer(
+                self.rect_buffer.as_ref().unwrap(),
+                offset,
+                bytemuck::cast_slice(&instances[first_changed * RECT_FLOATS..]),
+            );
+        }
 
-        // Calculate the new rect count
-        let new_rects_count = rects.len() / 6; // Each rectangle has 6 values
-        let new_total_count = current_rect_count + (new_rects_count as u32);
+        // Update the rect count
+        self.rect_count = Some(self.sorted_rects.len() as u32);
+    }
 
-        // Check if we need to resize the buffer
-        if let Some(rect_buffer) = &self.rect_buffer {
-            let current_buffer_size = rect_buffer.size();
-            let needed_size = (new_total_count as usize * 6 * std::mem::size_of::<f32>()) as u64;
+    // Where a line of text sits, kept absolute like the rects' centers so it survives the origin moving
+    fn text_anchor(&self, x: f32, fixed: f32) -> f64 {
+        if fixed == 0.0 {
+            self.sorted_rects.origin as f64 + x as f64
+        } else {
+            x as f64
+        }
+    }
 
-            if needed_size > current_buffer_size {
-                // Need to create a larger buffer
-                let new_buffer = gfx.device.create_buffer(&BufferDescriptor {
-                    label: Some("Rectangle Buffer (Resized)"),
-                    // Allocate some extra space to avoid frequent resizing
-                    size: needed_size * 2,
-                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-                    mapped_at_creation: false,
-                });
+    // New text is appended to the end of the text buffer. Removing a line rewrites the whole buffer, text is
+    // rare enough on the timeline that this stays cheap.
+    fn update_text(
+        &mut self,
+        texts: Vec<Queued_Text>,
+        removed_texts: Vec<(f64, String, [f32; 5])>,
+        gfx: &mut Graphics,
+    ) {
+        let mut removed_any = false;
+        for (anchor, text, style) in removed_texts {
+            // lines that aren't there are ignored, like removed rects
+            let found = self.queued_text.iter().position(|queued| {
+                queued.anchor == anchor && queued.text == text && queued.style == style
+            });
+            if let Some(index) = found {
+                self.queued_text.remove(index);
+                removed_any = true;
+            }
+        }
 
-                // Create a command encoder to copy the old data
-                let mut encoder = gfx
-                    .device
-                    .create_command_encoder(&CommandEncoderDescriptor {
-                        label: Some("Buffer Copy Encoder"),
-                    });
+        if removed_any {
+            self.queued_text.extend(texts);
+            self.rewrite_text(gfx);
+        } else {
+            let glyphs: Vec<f32> = texts
+                .iter()
+                .flat_map(|text| text.glyphs.iter().copied())
+                .collect();
+            self.queued_text.extend(texts);
+            if !glyphs.is_empty() {
+                append_instances(
+                    &mut self.text_buffer,
+                    &mut self.text_count,
+                    &glyphs,
+                    GLYPH_FLOATS,
+                    "Text Buffer",
+                    gfx,
+                );
+            }
+        }
+    }
 
-                // Copy existing data to the new buffer
-                encoder.copy_buffer_to_buffer(rect_buffer, 0, &new_buffer, 0, current_buffer_size);
+    // Writes every queued line back into the text buffer from the start
+    fn rewrite_text(&mut self, gfx: &mut Graphics) {
+        let glyphs: Vec<f32> = self
+            .queued_text
+            .iter()
+            .flat_map(|text| text.glyphs.iter().copied())
+            .collect();
+        // appending to an empty buffer reuses it when it's big enough and keeps it copyable for later appends
+        self.text_count = Some(0);
+        if !glyphs.is_empty() {
+            append_instances(
+                &mut self.text_buffer,
+                &mut self.text_count,
+                &glyphs,
+                GLYPH_FLOATS,
+                "Text Buffer",
+                gfx,
+            );
+        }
+    }
 
-                // Submit the copy command
-                gfx.queue.submit(std::iter::once(encoder.finish()));
+    // The runs of rects in the rect buffer that can be on screen
+    pub fn visible_rects(&self, camera: &Camera) -> Vec<Range<u32>> {
+        // narrow rects are widened by the shader, so they can poke into view from just off screen
+        let margin = camera.scale + camera.px_to_ms(MIN_WIDTH_PX);
+        self.sorted_rects
+            .visible(camera.pos - margin, camera.pos + margin)
+            .into_iter()
+            .map(|run| run.start as u32..run.end as u32)
+            .collect()
+    }
 
-                // Write the new data to the new buffer
-                let offset = (current_rect_count * 6 * std::mem::size_of::<f32>() as u32) as u64;
-                gfx.queue
-                    .write_buffer(&new_buffer, offset, bytemuck::cast_slice(&rects));
+    // Moves the point rect and text positions are measured from, it has to match Camera::init_pos
+    pub fn rebase(&mut self, origin: i64, gfx: &mut Graphics) {
+        self.sorted_rects.rebase(origin);
+        if let Some(rect_buffer) = &self.rect_buffer {
+            gfx.queue.write_buffer(
+                rect_buffer,
+                0,
+                bytemuck::cast_slice(&self.sorted_rects.instances),
+            );
+        }
 
-                // Replace the old buffer with the new one
-                self.rect_buffer = Some(new_buffer);
-            } else {
-                // Buffer is large enough, just write the new data
-                let offset = (current_rect_count * 6 * std::mem::size_of::<f32>() as u32) as u64;
-                gfx.queue
-                    .write_buffer(rect_buffer, offset, bytemuck::cast_slice(&rects));
+        let mut moved_text = false;
+        for text in &mut self.queued_text {
+            if text.style[4] == 0.0 {
+                let x = (text.anchor - origin as f64) as f32;
+                for glyph in text.glyphs.chunks_exact_mut(GLYPH_FLOATS) {
+                    glyph[0] = x;
+                }
+                moved_text = true;
             }
         }
-
-        // Update the rect count
-        self.rect_count = Some(new_total_count);
+        if moved_text {
+            self.rewrite_text(gfx);
+        }
     }
 
     fn append_colors(&mut self, colors: Vec<f32>, gfx: &mut plinth_core::graphics::Graphics) {
@@ -506,4 +756,395 @@ impl GPU_Resources {
             }
         }
     }
+
+    // Unlike the rect buffer, the overlay is rewritten from scratch every time it changes.
+    pub fn write_overlay(
+        &mut self,
+        overlay: Vec<GPU_Data>,
+        gfx: &mut plinth_core::graphics::Graphics,
+    ) {
+        let mut rects = vec![];
+        let mut glyphs = vec![];
+        for data in overlay {
+            match data {
+                GPU_Data::Rect {
+                    x,
+                    y,
+                    w,
+                    h,
+                    color_index,
+                    fixed,
+                } => {
+                    rects.extend_from_slice(&[x, y, w, h, color_index, fixed, 0.0]);
+                }
+                GPU_Data::Outline {
+                    x,
+                    y,
+                    w,
+                    h,
+                    color_index,
+                    fixed,
+                    thickness,
+                } => {
+                    rects.extend_from_slice(&[x, y, w, h, color_index, fixed, thickness]);
+                }
+                GPU_Data::Span { start, end, lane } => {
+                    let (instance, _) = span_instance(start, end, lane, self.sorted_rects.origin);
+                    rects.extend_from_slice(&instance);
+                }
+                GPU_Data::Text {
+                    text,
+                    x,
+                    y,
+                    size,
+                    align,
+                    color_index,
+                    fixed,
+                } => {
+                    glyphs.extend(self.glyph_atlas.layout(
+                        &text,
+                        x,
+                        y,
+                        size,
+                        align,
+                        color_index,
+                        fixed,
+                    ));
+                }
+                // the palette is shared with the rect buffer, colors belong in the regular queue, and the overlay
+                // never keeps spans or text around to remove
+                GPU_Data::Color { .. }
+                | GPU_Data::RemoveSpan { .. }
+                | GPU_Data::RemoveText { .. } => {}
+            }
+        }
+
+        write_instances(
+            &mut self.overlay_buffer,
+            &mut self.overlay_count,
+            &rects,
+            RECT_FLOATS,
+            "Overlay Buffer",
+            gfx,
+        );
+        write_instances(
+            &mut self.overlay_text_buffer,
+            &mut self.overlay_text_count,
+            &glyphs,
+            GLYPH_FLOATS,
+            "Overlay Text Buffer",
+            gfx,
+        );
+    }
+
+    pub fn init_text_shader(&mut self, gfx: &mut Graphics) {
+        self.text_shader = Some(gfx.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/text_shader.wgsl"))),
+        }));
+    }
+
+    pub fn init_atlas(&mut self, gfx: &mut Graphics) {
+        let device = &gfx.device;
+
+        // Single channel texture, each texel is the glyph coverage at that point
+        let atlas_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let atlas_view = atlas_texture.create_view(&TextureViewDescriptor::default());
+        let atlas_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Glyph Atlas Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let atlas_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Glyph Atlas Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let atlas_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Glyph Atlas Bind Group"),
+            layout: &atlas_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&atlas_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&atlas_sampler),
+                },
+            ],
+        });
+
+        self.atlas_texture = Some(atlas_texture);
+        self.atlas_bind_group_layout = Some(atlas_bind_group_layout);
+        self.atlas_bind_group = Some(atlas_bind_group);
+    }
+
+    // Copy any glyphs rasterized since the last frame into the atlas texture
+    pub fn flush_atlas(&mut self, gfx: &mut Graphics) {
+        let Some(atlas_texture) = &self.atlas_texture else {
+            return;
+        };
+
+        for glyph in self.glyph_atlas.pending.drain(..) {
+            if glyph.width == 0 || glyph.height == 0 {
+                continue;
+            }
+            gfx.queue.write_texture(
+                TexelCopyTextureInfo {
+                    texture: atlas_texture,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: glyph.x,
+                        y: glyph.y,
+                        z: 0,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                &glyph.pixels,
+                TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(glyph.width),
+                    rows_per_image: Some(glyph.height),
+                },
+                Extent3d {
+                    width: glyph.width,
+                    height: glyph.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    pub fn init_text_pipeline_layout(&mut self, gfx: &mut Graphics) {
+        // Make sure both bind group layouts are initialized
+        if self.combined_bind_group_layout.is_none() || self.atlas_bind_group_layout.is_none() {
+            panic!("Combined and atlas bind group layouts must be initialized before text pipeline layout");
+        }
+
+        self.text_pipeline_layout = Some(gfx.device.create_pipeline_layout(
+            &PipelineLayoutDescriptor {
+                label: Some("Text Pipeline Layout"),
+                bind_group_layouts: &[
+                    self.combined_bind_group_layout.as_ref().unwrap(),
+                    self.atlas_bind_group_layout.as_ref().unwrap(),
+                ],
+                push_constant_ranges: &[],
+            },
+        ));
+    }
+
+    pub fn init_text_pipeline(&mut self, gfx: &mut Graphics) {
+        let shader = self.text_shader.as_ref().unwrap();
+        let pipeline_layout = self.text_pipeline_layout.as_ref().unwrap();
+        let float = std::mem::size_of::<f32>() as u64;
+
+        // Define the vertex buffer layout, one instance per glyph
+        let glyph_buffer_layout = VertexBufferLayout {
+            array_stride: GLYPH_FLOATS as u64 * float,
+            step_mode: VertexStepMode::Instance,
+            attributes: &[
+                // Baseline position (x, y)
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                // Offset from the baseline in pixels
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 2 * float,
+                    shader_location: 1,
+                },
+                // Size in pixels (w, h)
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 4 * float,
+                    shader_location: 2,
+                },
+                // Top left corner in the atlas
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 6 * float,
+                    shader_location: 3,
+                },
+                // Size in the atlas
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 8 * float,
+                    shader_location: 4,
+                },
+                // Color index
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 10 * float,
+                    shader_location: 5,
+                },
+                // Fixed (bool)
+                wgpu::VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 11 * float,
+                    shader_location: 6,
+                },
+            ],
+        };
+
+        self.text_pipeline = Some(
+            gfx.device
+                .create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some("Text Pipeline"),
+                    layout: Some(pipeline_layout),
+                    vertex: VertexState {
+                        module: shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[glyph_buffer_layout],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(FragmentState {
+                        module: shader,
+                        entry_point: Some("fs_main"),
+                        // glyph edges are partially covered so text needs blending, unlike rects
+                        targets: &[Some(ColorTargetState {
+                            format: gfx.surface_config.format,
+                            blend: Some(BlendState::ALPHA_BLENDING),
+                            write_mask: ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: Default::default(),
+                    depth_stencil: None,
+                    multisample: Default::default(),
+                    multiview: None,
+                    cache: None,
+                }),
+        );
+    }
+}
+
+// Appends instance data to a vertex buffer, growing it when it runs out of room.
+fn append_instances(
+    buffer: &mut Option<wgpu::Buffer>,
+    count: &mut Option<u32>,
+    data: &[f32],
+    stride: usize,
+    label: &str,
+    gfx: &mut Graphics,
+) {
+    let float = std::mem::size_of::<f32>();
+    let current_count = count.unwrap_or(0) as usize;
+    let current_size = (current_count * stride * float) as u64;
+    let needed_size = current_size + (data.len() * float) as u64;
+
+    let too_small = buffer
+        .as_ref()
+        .map_or(true, |buffer| buffer.size() < needed_size);
+    if too_small {
+        let new_buffer = gfx.device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            // Allocate some extra space to avoid frequent resizing
+            size: needed_size * 2,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        // Copy existing data to the new buffer
+        if let Some(old_buffer) = buffer {
+            if current_size > 0 {
+                let mut encoder = gfx
+                    .device
+                    .create_command_encoder(&CommandEncoderDescriptor {
+                        label: Some("Buffer Copy Encoder"),
+                    });
+                encoder.copy_buffer_to_buffer(old_buffer, 0, &new_buffer, 0, current_size);
+                gfx.queue.submit(std::iter::once(encoder.finish()));
+            }
+        }
+        *buffer = Some(new_buffer);
+    }
+
+    gfx.queue.write_buffer(
+        buffer.as_ref().unwrap(),
+        current_size,
+        bytemuck::cast_slice(data),
+    );
+    *count = Some((current_count + data.len() / stride) as u32);
+}
+
+// Replaces the contents of a vertex buffer, only reallocating when the new data doesn't fit.
+fn write_instances(
+    buffer: &mut Option<wgpu::Buffer>,
+    count: &mut Option<u32>,
+    data: &[f32],
+    stride: usize,
+    label: &str,
+    gfx: &mut Graphics,
+) {
+    let needed_size = (data.len() * std::mem::size_of::<f32>()) as u64;
+    let too_small = buffer
+        .as_ref()
+        .map_or(true, |buffer| buffer.size() < needed_size);
+    if too_small && needed_size > 0 {
+        *buffer = Some(gfx.device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            // Allocate some extra space to avoid frequent resizing
+            size: needed_size * 2,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    }
+
+    if let Some(buffer) = buffer {
+        gfx.queue
+            .write_buffer(buffer, 0, bytemuck::cast_slice(data));
+    }
+    *count = Some((data.len() / stride) as u32);
+}
+
+// Rect instance for a span of time, positioned relative to origin. Offsets are computed in i64 so they stay exact
+// until the final conversion. Also returns the timestamp at the center of the span.
+fn span_instance(start: i64, end: i64, lane: usize, origin: i64) -> ([f32; RECT_FLOATS], f64) {
+    let center = start as f64 + (end - start) as f64 / 2.0;
+    let x = ((start - origin) as f64 + (end - start) as f64 / 2.0) as f32;
+    let instance = [
+        x,
+        lane as f32,
+        (end - start) as f32,
+        LOG_RECT_HEIGHT,
+        LOG_COLOR,
+        0.0,
+        0.0,
+    ];
+    (instance, center)
 }
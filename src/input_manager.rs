@@ -1,14 +1,66 @@
+use std::collections::{HashMap, VecDeque};
+
+use plinth_util::time::now;
 use winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, MouseButton},
+    event::{ElementState, MouseButton, Touch, TouchPhase},
+    keyboard::{Key, ModifiersState},
+};
+
+use crate::{
+    camera,
+    camera_animation::Easing,
+    keymap::Action,
+    lanes::Lanes,
+    my_app::MyApp,
+    picking::{pick, Pick},
 };
 
-use crate::{camera, my_app::MyApp};
+// Cursor movement (in pixels) between press and release that turns a click into a drag
+const DRAG_THRESHOLD: f64 = 4.0;
+
+// Two taps closer together than this, in time and space, are a double tap
+const DOUBLE_TAP_MS: f64 = 300.0;
+const DOUBLE_TAP_DISTANCE: f64 = 30.0;
+const DOUBLE_TAP_ZOOM: f64 = 2.0;
+const DOUBLE_TAP_ANIMATION_MS: f64 = 250.0;
+
+// Keyboard navigation. Pans move by a share of the view width, zoom steps multiply the scale.
+const KEY_PAN_FRACTION: f64 = 0.1;
+const KEY_PAN_Y_PX: f64 = 40.0;
+const KEY_ZOOM_STEP: f64 = 1.5;
+const KEY_STEP_MS: f64 = 150.0; // how long small steps glide for
+const KEY_JUMP_MS: f64 = 400.0; // and bigger jumps
+
+static ms_per_hour: f64 = 1000.0 * 60.0 * 60.0;
+static ms_per_day: f64 = ms_per_hour * 24.0;
+
+// Kinetic scrolling. Drag velocity is measured over the last VELOCITY_WINDOW_MS of movement, and the view keeps
+// coasting at that speed after release, losing speed with a time constant of FRICTION_MS.
+const VELOCITY_WINDOW_MS: f64 = 80.0;
+const FRICTION_MS: f64 = 325.0;
+const OVERSHOOT_FRICTION_MS: f64 = 60.0; // much stickier past the edges of the logs
+const MIN_VELOCITY: f64 = 0.02; // px per ms, slower than this just stops
+
+// A pause longer than this before release means the drag was stopped on purpose
+const MAX_RELEASE_PAUSE_MS: f64 = 50.0;
 
 pub struct Input_State {
     pub left: bool,
     pub right: bool,
     pub cursor_pos: PhysicalPosition<f64>,
+    pub cursor_inside: bool,
+    pub press_pos: PhysicalPosition<f64>, // where the left button went down
+    pub dragged: bool, // whether the cursor moved far enough since then to count as a drag
+    pub hover: Option<Pick>, // log or session under the cursor, updated with the overlay
+    pub selected: Option<Pick>,
+    pub modifiers: ModifiersState, // scrolling with shift pans the lanes, with alt it zooms them
+    pub drag_samples: VecDeque<(f64, f64)>, // (time, x) of recent cursor positions while dragging
+    pub velocity: f64,             // px per ms the view keeps panning at after a drag is released
+    pub touches: HashMap<u64, PhysicalPosition<f64>>, // fingers on the screen, by touch id
+    pub touch_start: PhysicalPosition<f64>, // where the current gesture's first finger went down
+    pub touch_moved: bool,         // whether the current gesture has been anything other than a tap
+    pub last_tap: Option<(PhysicalPosition<f64>, f64)>, // where and when, handled once it can't be a double tap
 }
 
 impl Input_State {
@@ -17,30 +69,144 @@ impl Input_State {
             left: false,
             right: false,
             cursor_pos: PhysicalPosition::new(0.0, 0.0),
+            cursor_inside: false,
+            press_pos: PhysicalPosition::new(0.0, 0.0),
+            dragged: false,
+            hover: None,
+            selected: None,
+            modifiers: ModifiersState::empty(),
+            drag_samples: VecDeque::new(),
+            velocity: 0.0,
+            touches: HashMap::new(),
+            touch_start: PhysicalPosition::new(0.0, 0.0),
+            touch_moved: false,
+            last_tap: None,
+        }
+    }
+
+    fn sample_drag(&mut self, x: f64) {
+        let time = now();
+        self.drag_samples.push_back((time, x));
+        while let Some((oldest, _)) = self.drag_samples.front() {
+            if time - oldest > VELOCITY_WINDOW_MS {
+                self.drag_samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Middle of all the fingers on the screen and their average distance from it
+    fn touch_centroid(&self) -> (PhysicalPosition<f64>, f64) {
+        let count = self.touches.len().max(1) as f64;
+        let (sum_x, sum_y) = self
+            .touches
+            .values()
+            .fold((0.0, 0.0), |(x, y), touch| (x + touch.x, y + touch.y));
+        let center = PhysicalPosition::new(sum_x / count, sum_y / count);
+        let spread = self
+            .touches
+            .values()
+            .map(|touch| (touch.x - center.x).hypot(touch.y - center.y))
+            .sum::<f64>()
+            / count;
+        (center, spread)
+    }
+
+    // Average horizontal speed over the recent drag samples, 0 if the cursor was held still before release
+    fn release_velocity(&self) -> f64 {
+        match (self.drag_samples.front(), self.drag_samples.back()) {
+            (Some((first_time, first_x)), Some((last_time, last_x)))
+                if last_time > first_time && now() - last_time < MAX_RELEASE_PAUSE_MS =>
+            {
+                (last_x - first_x) / (last_time - first_time)
+            }
+            _ => 0.0,
         }
     }
 }
 
 pub trait Input_Manager {
+    fn wheel(&mut self, dx: f64, dy: f64);
     fn v_scroll(&mut self, delta: f64);
     fn h_scroll(&mut self, delta: f64);
     fn click(&mut self, button: &MouseButton, state: &ElementState);
     fn cursor_moved(&mut self, position: PhysicalPosition<f64>);
+    fn cursor_left(&mut self);
+    fn coast(&mut self, dt: f64);
+    fn key_pressed(&mut self, key: &Key);
+    fn pinch(&mut self, delta: f64);
+    fn touch(&mut self, touch: &Touch);
+    fn tap(&mut self, position: PhysicalPosition<f64>);
+    fn settle_tap(&mut self);
 }
 
 // A nice place to store input handling logic, cleaner when insulated from the huge match statement in plinth_app.rs
 impl Input_Manager for MyApp {
+    fn wheel(&mut self, dx: f64, dy: f64) {
+        // browsers turn shift + wheel into horizontal scrolling, but we want it vertical
+        if self.input_state.modifiers.shift_key() {
+            self.v_scroll(if dy != 0.0 { dy } else { dx });
+        } else if dx.abs() > dy.abs() {
+            self.h_scroll(-dx);
+        } else {
+            self.v_scroll(dy);
+        }
+    }
+
     fn v_scroll(&mut self, delta: f64) {
-        self.camera.zoom(delta, self.input_state.cursor_pos.x);
+        self.input_state.velocity = 0.0;
+        if self.input_state.modifiers.shift_key() {
+            self.camera.pan_y(delta);
+        } else if self.input_state.modifiers.alt_key() {
+            self.camera.zoom_y(delta, self.input_state.cursor_pos.y);
+        } else {
+            self.camera.zoom(delta, self.input_state.cursor_pos.x);
+            return;
+        }
+        self.camera.clamp_y(
+            self.data.lanes.content_height(),
+            Lanes::visible_height(&self.camera),
+        );
     }
 
     fn h_scroll(&mut self, delta: f64) {
+        self.input_state.velocity = 0.0;
         self.camera.pan(delta);
     }
 
     fn click(&mut self, button: &MouseButton, state: &ElementState) {
         match button {
-            MouseButton::Left => self.input_state.left = state == &ElementState::Pressed,
+            MouseButton::Left => {
+                let pressed = state == &ElementState::Pressed;
+                if pressed {
+                    // grabbing the view stops it coasting
+                    self.input_state.velocity = 0.0;
+                    self.input_state.drag_samples.clear();
+                    self.input_state.press_pos = self.input_state.cursor_pos;
+                    self.input_state.dragged = false;
+                } else if self.input_state.left && self.input_state.dragged {
+                    self.input_state.velocity = self.input_state.release_velocity();
+                } else if self.input_state.left && !self.input_state.dragged {
+                    // clicking a lane header folds the lane away or opens it back up
+                    if let Some((lane, true)) = self
+                        .data
+                        .lanes
+                        .lane_at(self.input_state.cursor_pos.y, &self.camera)
+                    {
+                        self.data.lanes.toggle_collapsed(lane);
+                        self.input_state.left = false;
+                        self.overlay_dirty = true;
+                        return;
+                    }
+                    // a click without a drag selects whatever is under the cursor, or clears the selection
+                    let now = now() as i64;
+                    let picked = pick(&self.data, &self.camera, self.input_state.cursor_pos, now);
+                    self.select(picked);
+                }
+                self.input_state.left = pressed;
+                self.overlay_dirty = true;
+            }
             MouseButton::Right => self.input_state.right = state == &ElementState::Pressed,
             _ => {}
         }
@@ -50,7 +216,242 @@ impl Input_Manager for MyApp {
         if self.input_state.left {
             let dx = position.x - self.input_state.cursor_pos.x;
             self.camera.pan(dx);
+            self.input_state.sample_drag(position.x);
+
+            let (press_x, press_y) = (self.input_state.press_pos.x, self.input_state.press_pos.y);
+            if (position.x - press_x).hypot(position.y - press_y) > DRAG_THRESHOLD {
+                self.input_state.dragged = true;
+            }
         }
         self.input_state.cursor_pos = position;
+        self.input_state.cursor_inside = true;
+        // the hovered log depends on the cursor, so hit testing happens when the overlay is rebuilt
+        self.overlay_dirty = true;
+    }
+
+    fn cursor_left(&mut self) {
+        self.input_state.cursor_inside = false;
+        self.overlay_dirty = true;
+    }
+
+    fn key_pressed(&mut self, key: &Key) {
+        // leave browser shortcuts alone
+        let modifiers = self.input_state.modifiers;
+        if modifiers.control_key() || modifiers.super_key() {
+            return;
+        }
+        let Some(action) = self.keymap.action(key) else {
+            return;
+        };
+        self.input_state.velocity = 0.0;
+
+        let (pos, scale) = self.camera.target();
+        match action {
+            Action::PanLeft => self.camera.animate_to(
+                pos - scale * 2.0 * KEY_PAN_FRACTION,
+                scale,
+                KEY_STEP_MS,
+                Easing::EaseOut,
+            ),
+            Action::PanRight => self.camera.animate_to(
+                pos + scale * 2.0 * KEY_PAN_FRACTION,
+                scale,
+                KEY_STEP_MS,
+                Easing::EaseOut,
+            ),
+            Action::PanUp | Action::PanDown => {
+                let dy = if action == Action::PanUp {
+                    KEY_PAN_Y_PX
+                } else {
+                    -KEY_PAN_Y_PX
+                };
+                self.camera.pan_y(dy);
+                self.camera.clamp_y(
+                    self.data.lanes.content_height(),
+                    Lanes::visible_height(&self.camera),
+                );
+            }
+            Action::ZoomIn => {
+                self.camera
+                    .zoom_to(scale / KEY_ZOOM_STEP, KEY_STEP_MS, Easing::EaseOut)
+            }
+            Action::ZoomOut => {
+                self.camera
+                    .zoom_to(scale * KEY_ZOOM_STEP, KEY_STEP_MS, Easing::EaseOut)
+            }
+            // now goes near the right edge, where new logs show up
+            Action::JumpToNow => {
+                self.camera
+                    .animate_to(now() - scale * 0.8, scale, KEY_JUMP_MS, Easing::EaseInOut)
+            }
+            Action::Follow => {
+                if self.camera.following {
+                    self.camera.following = false;
+                } else {
+                    self.camera.follow();
+                }
+            }
+            Action::PageBack => {
+                self.camera
+                    .animate_to(pos - scale * 2.0, scale, KEY_JUMP_MS, Easing::EaseInOut)
+            }
+            Action::PageForward => {
+                self.camera
+                    .animate_to(pos + scale * 2.0, scale, KEY_JUMP_MS, Easing::EaseInOut)
+            }
+            Action::SpanHour
+            | Action::SpanDay
+            | Action::SpanWeek
+            | Action::SpanMonth
+            | Action::SpanYear => {
+                let width = match action {
+                    Action::SpanHour => ms_per_hour,
+                    Action::SpanDay => ms_per_day,
+                    Action::SpanWeek => 7.0 * ms_per_day,
+                    Action::SpanMonth => 30.0 * ms_per_day,
+                    _ => 365.0 * ms_per_day,
+                };
+                self.camera
+                    .zoom_to(width / 2.0, KEY_JUMP_MS, Easing::EaseInOut)
+            }
+        }
+    }
+
+    // Trackpad pinch, delta is how much the fingers spread apart as a share of their distance
+    fn pinch(&mut self, delta: f64) {
+        self.input_state.velocity = 0.0;
+        self.camera
+            .zoom_by(1.0 / (1.0 + delta).max(0.1), self.input_state.cursor_pos.x);
+    }
+
+    // One finger drags the view, two pinch to zoom around the middle of the fingers. A tap selects, once it's
+    // clear it isn't the start of a double tap, and a double tap zooms in.
+    fn touch(&mut self, touch: &Touch) {
+        let state = &mut self.input_state;
+        match touch.phase {
+            TouchPhase::Started => {
+                state.touches.insert(touch.id, touch.location);
+                state.velocity = 0.0;
+                state.drag_samples.clear();
+                if state.touches.len() == 1 {
+                    state.touch_start = touch.location;
+                    state.touch_moved = false;
+                } else {
+                    state.touch_moved = true;
+                }
+            }
+            TouchPhase::Moved => {
+                let Some(previous) = state.touches.get(&touch.id).copied() else {
+                    return;
+                };
+                if state.touches.len() == 1 {
+                    state.touches.insert(touch.id, touch.location);
+                    state.sample_drag(touch.location.x);
+                    let (start_x, start_y) = (state.touch_start.x, state.touch_start.y);
+                    if (touch.location.x - start_x).hypot(touch.location.y - start_y)
+                        > DRAG_THRESHOLD
+                    {
+                        state.touch_moved = true;
+                    }
+                    self.camera.pan(touch.location.x - previous.x);
+                } else {
+                    let (old_center, old_spread) = state.touch_centroid();
+                    state.touches.insert(touch.id, touch.location);
+                    let (center, spread) = state.touch_centroid();
+                    // the time under the fingers follows them, and spreading them zooms in around it
+                    self.camera.pan(center.x - old_center.x);
+                    if old_spread > 0.0 && spread > 0.0 {
+                        self.camera.zoom_by(old_spread / spread, center.x);
+                    }
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if state.touches.remove(&touch.id).is_none() {
+                    return;
+                }
+                // the remaining finger starts a fresh drag, rather than flinging with the pinch's movement
+                state.drag_samples.clear();
+                if !state.touches.is_empty() {
+                    return;
+                }
+                if state.touch_moved {
+                    state.velocity = state.release_velocity();
+                } else if touch.phase == TouchPhase::Ended {
+                    self.tap(touch.location);
+                }
+            }
+        }
+        self.overlay_dirty = true;
+    }
+
+    fn tap(&mut self, position: PhysicalPosition<f64>) {
+        let time = now();
+        let double = self.input_state.last_tap.is_some_and(|(last, last_time)| {
+            time - last_time < DOUBLE_TAP_MS
+                && (position.x - last.x).hypot(position.y - last.y) < DOUBLE_TAP_DISTANCE
+        });
+        if double {
+            // zoom in around the tap, keeping the time under the finger where it is
+            self.input_state.last_tap = None;
+            let tapped = self.camera.screen_to_time(position.x);
+            let (pos, scale) = self.camera.target();
+            self.camera.animate_to(
+                tapped + (pos - tapped) / DOUBLE_TAP_ZOOM,
+                scale / DOUBLE_TAP_ZOOM,
+                DOUBLE_TAP_ANIMATION_MS,
+                Easing::EaseOut,
+            );
+            return;
+        }
+
+        // a single tap waits for settle_tap, so the first half of a double tap doesn't select anything
+        self.input_state.last_tap = Some((position, time));
+    }
+
+    // Handles the last tap as a single tap once too long has passed for a second one to make it a double tap
+    fn settle_tap(&mut self) {
+        let time = now();
+        let Some((position, _)) = self
+            .input_state
+            .last_tap
+            .filter(|(_, last_time)| time - last_time >= DOUBLE_TAP_MS)
+        else {
+            return;
+        };
+        self.input_state.last_tap = None;
+
+        if let Some((lane, true)) = self.data.lanes.lane_at(position.y, &self.camera) {
+            self.data.lanes.toggle_collapsed(lane);
+            self.overlay_dirty = true;
+            return;
+        }
+        let picked = pick(&self.data, &self.camera, position, time as i64);
+        self.select(picked);
+    }
+
+    // Keeps the view moving after a drag is let go, slowing down over time
+    fn coast(&mut self, dt: f64) {
+        let velocity = self.input_state.velocity;
+        if velocity == 0.0 || self.input_state.left {
+            return;
+        }
+        // a jump or fit that started since takes over
+        if self.camera.animation.is_some() {
+            self.input_state.velocity = 0.0;
+            return;
+        }
+        self.camera.pan(velocity * dt);
+
+        let friction = if self.camera.overshoot() != 0.0 {
+            OVERSHOOT_FRICTION_MS
+        } else {
+            FRICTION_MS
+        };
+        let velocity = velocity * (-dt / friction).exp();
+        self.input_state.velocity = if velocity.abs() < MIN_VELOCITY {
+            0.0
+        } else {
+            velocity
+        };
     }
 }
@@ -1,19 +1,379 @@
-use crate::global_app::app_mut;
-use js_sys::Uint8Array;
-use plinth_util::logging::log;
-use wasm_bindgen::prelude::wasm_bindgen;
+use crate::{
+    data::Data,
+    global_app::{app, app_mut},
+    keymap::Action,
+    log_cache,
+    log_parser::{parse_body, Parsed_Body},
+    picking::Pick,
+    view_state::View_State,
+};
+use js_sys::{Array, Function, Object, Reflect, Uint8Array};
+use plinth_util::{
+    logging::log,
+    time::{format_timestamp, now},
+};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 #[wasm_bindgen]
 pub fn receive_body(body_bytes: Uint8Array) {
-    let body_vec = body_bytes.to_vec();
-    let body = match String::from_utf8(body_vec) {
-        Ok(string) => string,
-        Err(e) => {
-            log("Failed to convert log body to string. Fn: receive_logs()");
-
-            "".to_string()
-        }
+    let parsed = parse_body(&body_bytes.to_vec(), now());
+    let mut app = app_mut().unwrap();
+    app.report_parse_errors("logLoad", &parsed);
+    app.receive_logs(parsed.logs);
+}
+
+// Logs pushed over the stream arrive as text, a batch of them one per line
+#[wasm_bindgen]
+pub fn receive_text(body: String) {
+    let parsed = parse_body(body.as_bytes(), now());
+    let mut app = app_mut().unwrap();
+    app.log_cache.save(&[], &parsed.accepted);
+    app.report_parse_errors("the log stream", &parsed);
+    app.receive_logs(parsed.logs);
+}
+
+// Opens the offline cache and shows whatever is in it, then calls on_ready. Ranges shouldn't be requested until
+// then, or everything the cache already has gets fetched again.
+#[wasm_bindgen]
+pub fn open_cache(on_ready: Function) {
+    log_cache::open(on_ready);
+}
+
+// The page passes a function here to be called with (start, end) timestamps whenever the view needs logs from
+// a time range it doesn't have yet. The page answers with receive_range or range_failed.
+#[wasm_bindgen]
+pub fn set_range_request_callback(callback: Function) {
+    app_mut().unwrap().range_request_callback = Some(callback);
+}
+
+// The logs for a requested range, everything from start to end
+#[wasm_bindgen]
+pub fn receive_range(body_bytes: Uint8Array, start: f64, end: f64) {
+    let parsed = parse_body(&body_bytes.to_vec(), now());
+    let mut app = app_mut().unwrap();
+    // even with lines skipped the range counts as loaded, fetching it again would only skip them again
+    app.log_cache.save(&[(start, end)], &parsed.accepted);
+    app.report_parse_errors(
+        &format!(
+            "logs from {} to {}",
+            format_timestamp(start as i64),
+            format_timestamp(end as i64)
+        ),
+        &parsed,
+    );
+    app.receive_range(parsed.logs, start, end);
+}
+
+// Every log the backend has, for when it can't be asked for a time range. All time up to now counts as loaded,
+// so nothing else is requested until it's forgotten.
+#[wasm_bindgen]
+pub fn receive_all(body_bytes: Uint8Array) {
+    let parsed = parse_body(&body_bytes.to_vec(), now());
+    let mut app = app_mut().unwrap();
+    let end = now();
+    app.log_cache
+        .save(&[(f64::NEG_INFINITY, end)], &parsed.accepted);
+    app.report_parse_errors("logLoad", &parsed);
+    app.receive_range(parsed.logs, f64::NEG_INFINITY, end);
+}
+
+#[wasm_bindgen]
+pub fn range_failed(start: f64, end: f64) {
+    app_mut().unwrap().range_loader.failed(start, end, now());
+}
+
+// Logs after this timestamp may have been missed, they'll be fetched again when they're in view
+#[wasm_bindgen]
+pub fn forget_loaded_after(timestamp: f64) {
+    app_mut().unwrap().range_loader.forget_after(timestamp);
+}
+
+// Whether the log stream is open. While it is, logs newer than what's been fetched arrive over it instead.
+#[wasm_bindgen]
+pub fn set_stream_connected(connected: bool) {
+    app_mut()
+        .unwrap()
+        .range_loader
+        .set_stream_connected(connected, now());
+}
+
+// The page passes a function here to be called with a report whenever lines of logs had to be skipped
+#[wasm_bindgen]
+pub fn set_parse_error_callback(callback: Function) {
+    app_mut().unwrap().parse_error_callback = Some(callback);
+}
+
+// The page passes a function here to be called with the selected log (or null) whenever the selection changes
+#[wasm_bindgen]
+pub fn set_selection_callback(callback: Function) {
+    app_mut().unwrap().selection_callback = Some(callback);
+}
+
+#[wasm_bindgen]
+pub fn clear_selection() {
+    app_mut().unwrap().select(None);
+}
+
+// Collapses the lane with this name, or expands it if it's already collapsed
+#[wasm_bindgen]
+pub fn toggle_lane(name: &str) {
+    let mut app = app_mut().unwrap();
+    if let Some(id) = app.data.lanes.id(name) {
+        app.data.lanes.toggle_collapsed(id);
+        app.overlay_dirty = true;
+    }
+}
+
+// Moves the lane with this name to a position in the display order, 0 is the top
+#[wasm_bindgen]
+pub fn move_lane(name: &str, index: usize) {
+    let mut app = app_mut().unwrap();
+    if let Some(id) = app.data.lanes.id(name) {
+        app.data.lanes.move_lane(id, index);
+        app.overlay_dirty = true;
+    }
+}
+
+// How far in and out the timeline can zoom, as the amount of time across the whole screen in ms
+#[wasm_bindgen]
+pub fn set_zoom_limits(min_width_ms: f64, max_width_ms: f64) {
+    app_mut()
+        .unwrap()
+        .camera
+        .set_zoom_limits(min_width_ms, max_width_ms);
+}
+
+// Whether the view springs back to the loaded logs when panned past them
+#[wasm_bindgen]
+pub fn set_pan_bounds(enabled: bool) {
+    app_mut().unwrap().camera.bounded = enabled;
+}
+
+// Makes a key do one of the keyboard actions, like bind_key("ArrowLeft", "pan_left"). Keys are named the
+// way KeyboardEvent.key names them. Returns false if the action doesn't exist.
+#[wasm_bindgen]
+pub fn bind_key(key: &str, action: &str) -> bool {
+    let Some(action) = Action::from_name(action) else {
+        return false;
+    };
+    app_mut().unwrap().keymap.bind(key, action);
+    true
+}
+
+#[wasm_bindgen]
+pub fn unbind_key(key: &str) {
+    app_mut().unwrap().keymap.unbind(key);
+}
+
+// Every bound key and the name of its action, as a plain object
+#[wasm_bindgen]
+pub fn key_bindings() -> Object {
+    let object = Object::new();
+    for (key, action) in &app_mut().unwrap().keymap.bindings {
+        let _ = Reflect::set(
+            &object,
+            &JsValue::from_str(key),
+            &JsValue::from_str(action.name()),
+        );
+    }
+    object
+}
+
+// Centers the view on a timestamp (ms since the epoch) without changing the zoom
+#[wasm_bindgen]
+pub fn jump_to_date(timestamp: f64) {
+    if !timestamp.is_finite() {
+        log(format!(
+            "Ignoring jump to a timestamp that isn't finite: {}. Fn: jump_to_date()",
+            timestamp
+        )
+        .as_str());
+        return;
+    }
+    app_mut().unwrap().camera.center_on(timestamp);
+}
+
+// Shows exactly the time from start to end across the screen. A reversed range is shown the right way around.
+#[wasm_bindgen]
+pub fn show_range(start: f64, end: f64) {
+    if !start.is_finite() || !end.is_finite() || start == end {
+        log(format!(
+            "Ignoring range that can't be shown: {} to {}. Fn: show_range()",
+            start, end
+        )
+        .as_str());
+        return;
+    }
+    app_mut().unwrap().camera.show_range(start, end);
+}
+
+// Fits every loaded log on screen
+#[wasm_bindgen]
+pub fn fit_all() {
+    let mut app = app_mut().unwrap();
+    if let Some((start, end)) = app.loaded_range() {
+        app.camera.fit_range(start, end);
+    }
+}
+
+// Fits the selected log or session on screen
+#[wasm_bindgen]
+pub fn fit_selection() {
+    let mut app = app_mut().unwrap();
+    if let Some((start, end)) = app
+        .input_state
+        .selected
+        .as_ref()
+        .map(|selected| (selected.start as f64, selected.end as f64))
+    {
+        app.camera.fit_range(start, end);
+    }
+}
+
+// Fits the logs with these timestamps on screen, for showing a handful of related logs together
+#[wasm_bindgen]
+pub fn fit_timestamps(timestamps: Vec<f64>) {
+    let start = timestamps.iter().copied().fold(f64::INFINITY, f64::min);
+    let end = timestamps.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if start.is_finite() && end.is_finite() {
+        app_mut().unwrap().camera.fit_range(start, end);
+    }
+}
+
+// Starts or stops keeping the current time pinned near the right edge
+#[wasm_bindgen]
+pub fn set_follow(follow: bool) {
+    let mut app = app_mut().unwrap();
+    if follow && !app.camera.following {
+        app.camera.follow();
+    } else if !follow {
+        app.camera.following = false;
+    }
+}
+
+#[wasm_bindgen]
+pub fn is_following() -> bool {
+    app().unwrap().camera.following
+}
+
+// The page passes a function here to be called with true or false whenever following starts or stops
+#[wasm_bindgen]
+pub fn set_follow_callback(callback: Function) {
+    app_mut().unwrap().follow_callback = Some(callback);
+}
+
+// The page passes a function here to be called with the view encoded for the url hash whenever it settles
+#[wasm_bindgen]
+pub fn set_view_state_callback(callback: Function) {
+    app_mut().unwrap().view_state_callback = Some(callback);
+}
+
+// Moves to the view in a url hash, returns false if there isn't one in it
+#[wasm_bindgen]
+pub fn restore_view_state(hash: &str) -> bool {
+    let Some(state) = View_State::decode(hash) else {
+        return false;
+    };
+    let mut app = app_mut().unwrap();
+    // the url already says this, no need to write it back
+    app.view_state_sent = state.encode();
+    app.restore_view_state(state);
+    true
+}
+
+// Lane names from top to bottom
+#[wasm_bindgen]
+pub fn lane_names() -> Array {
+    let app = app().unwrap();
+    let lanes = &app.data.lanes;
+    lanes
+        .order
+        .iter()
+        .map(|id| JsValue::from_str(&lanes.lanes[*id].name))
+        .collect()
+}
+
+// Number of sessions and total time per label, sorted by label, as [{ label, count, total_ms, running }].
+// Running sessions count up to now.
+#[wasm_bindgen]
+pub fn session_stats() -> Array {
+    app()
+        .unwrap()
+        .data
+        .sessions
+        .stats(now() as i64)
+        .iter()
+        .map(|stats| {
+            let object = Object::new();
+            let fields: [(&str, JsValue); 4] = [
+                ("label", JsValue::from_str(&stats.label)),
+                ("count", JsValue::from_f64(stats.count as f64)),
+                ("total_ms", JsValue::from_f64(stats.total_ms as f64)),
+                ("running", JsValue::from_bool(stats.running)),
+            ];
+            for (key, value) in fields {
+                let _ = Reflect::set(&object, &JsValue::from_str(key), &value);
+            }
+            JsValue::from(object)
+        })
+        .collect()
+}
+
+// Plain object with everything we know about the selection, null when nothing is selected
+pub fn selection_to_js(selected: Option<&Pick>, data: &Data) -> JsValue {
+    let Some(pick) = selected else {
+        return JsValue::NULL;
+    };
+    let Some(log) = data.logs.get(pick.log.0, &pick.log.1) else {
+        return JsValue::NULL;
     };
 
-    app_mut().unwrap().receive_logs(body);
+    let object = Object::new();
+    let fields: [(&str, JsValue); 6] = [
+        ("timestamp", JsValue::from_f64(log.timestamp as f64)),
+        ("time", JsValue::from_str(&format_timestamp(log.timestamp))),
+        ("label", JsValue::from_str(&log.label)),
+        ("start", JsValue::from_f64(pick.start as f64)),
+        ("end", JsValue::from_f64(pick.end as f64)),
+        (
+            "duration",
+            JsValue::from_f64((pick.end - pick.start) as f64),
+        ),
+    ];
+    for (key, value) in fields {
+        let _ = Reflect::set(&object, &JsValue::from_str(key), &value);
+    }
+    object.into()
+}
+
+// { source, lines, skipped, errors: [{ line, reason, text }] }, errors only has the first few when there are lots
+pub fn parse_report_to_js(source: &str, parsed: &Parsed_Body) -> JsValue {
+    let errors: Array = parsed
+        .errors
+        .iter()
+        .map(|error| {
+            let object = Object::new();
+            let fields: [(&str, JsValue); 3] = [
+                ("line", JsValue::from_f64(error.line as f64)),
+                ("reason", JsValue::from_str(&error.reason)),
+                ("text", JsValue::from_str(&error.text)),
+            ];
+            for (key, value) in fields {
+                let _ = Reflect::set(&object, &JsValue::from_str(key), &value);
+            }
+            JsValue::from(object)
+        })
+        .collect();
+
+    let object = Object::new();
+    let fields: [(&str, JsValue); 4] = [
+        ("source", JsValue::from_str(source)),
+        ("lines", JsValue::from_f64(parsed.lines as f64)),
+        ("skipped", JsValue::from_f64(parsed.error_count as f64)),
+        ("errors", errors.into()),
+    ];
+    for (key, value) in fields {
+        let _ = Reflect::set(&object, &JsValue::from_str(key), &value);
+    }
+    object.into()
 }
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use winit::keyboard::{Key, NamedKey};
+
+// Everything the keyboard can do to the view
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    JumpToNow,
+    Follow,
+    PageBack,
+    PageForward,
+    SpanHour,
+    SpanDay,
+    SpanWeek,
+    SpanMonth,
+    SpanYear,
+}
+
+static ACTIONS: [(Action, &str); 15] = [
+    (Action::PanLeft, "pan_left"),
+    (Action::PanRight, "pan_right"),
+    (Action::PanUp, "pan_up"),
+    (Action::PanDown, "pan_down"),
+    (Action::ZoomIn, "zoom_in"),
+    (Action::ZoomOut, "zoom_out"),
+    (Action::JumpToNow, "jump_to_now"),
+    (Action::Follow, "follow"),
+    (Action::PageBack, "page_back"),
+    (Action::PageForward, "page_forward"),
+    (Action::SpanHour, "span_hour"),
+    (Action::SpanDay, "span_day"),
+    (Action::SpanWeek, "span_week"),
+    (Action::SpanMonth, "span_month"),
+    (Action::SpanYear, "span_year"),
+];
+
+static DEFAULT_BINDINGS: [(&str, Action); 17] = [
+    ("ArrowLeft", Action::PanLeft),
+    ("ArrowRight", Action::PanRight),
+    ("ArrowUp", Action::PanUp),
+    ("ArrowDown", Action::PanDown),
+    ("+", Action::ZoomIn),
+    ("=", Action::ZoomIn), // + without shift on most layouts
+    ("-", Action::ZoomOut),
+    ("Home", Action::JumpToNow),
+    ("t", Action::JumpToNow),
+    ("f", Action::Follow), // toggles
+    ("PageUp", Action::PageBack),
+    ("PageDown", Action::PageForward),
+    ("1", Action::SpanHour),
+    ("2", Action::SpanDay),
+    ("3", Action::SpanWeek),
+    ("4", Action::SpanMonth),
+    ("5", Action::SpanYear),
+];
+
+// Names the browser gives the non character keys a binding can use. Anything else can't be bound.
+static NAMED_KEYS: [(NamedKey, &str); 13] = [
+    (NamedKey::ArrowLeft, "ArrowLeft"),
+    (NamedKey::ArrowRight, "ArrowRight"),
+    (NamedKey::ArrowUp, "ArrowUp"),
+    (NamedKey::ArrowDown, "ArrowDown"),
+    (NamedKey::Home, "Home"),
+    (NamedKey::End, "End"),
+    (NamedKey::PageUp, "PageUp"),
+    (NamedKey::PageDown, "PageDown"),
+    (NamedKey::Space, " "),
+    (NamedKey::Enter, "Enter"),
+    (NamedKey::Escape, "Escape"),
+    (NamedKey::Backspace, "Backspace"),
+    (NamedKey::Delete, "Delete"),
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(action, _)| action == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, action_name)| *action_name == name)
+            .map(|(action, _)| *action)
+    }
+}
+
+// Names keys the same way the browser's KeyboardEvent.key does, so the page can rebind them with the names it
+// already sees. Letters are lowercase so bindings don't depend on shift or caps lock.
+pub fn key_name(key: &Key) -> Option<String> {
+    match key {
+        Key::Named(named) => NAMED_KEYS
+            .iter()
+            .find(|(key, _)| key == named)
+            .map(|(_, name)| name.to_string()),
+        Key::Character(text) => Some(text.to_lowercase()),
+        _ => None,
+    }
+}
+
+// Single characters are stored lowercase to match key_name, named keys keep their case
+fn normalize_key(key: &str) -> String {
+    if key.chars().count() == 1 {
+        key.to_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+// Which key does what, starts out with the defaults and can be changed from the page
+pub struct Keymap {
+    pub bindings: HashMap<String, Action>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS
+                .iter()
+                .map(|(key, action)| (key.to_string(), *action))
+                .collect(),
+        }
+    }
+
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        self.bindings.get(&key_name(key)?).copied()
+    }
+
+    // A key can only do one thing, binding it again replaces what it did before
+    pub fn bind(&mut self, key: &str, action: Action) {
+        self.bindings.insert(normalize_key(key), action);
+    }
+
+    pub fn unbind(&mut self, key: &str) {
+        self.bindings.remove(&normalize_key(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_keys_like_the_browser() {
+        assert_eq!(
+            key_name(&Key::Named(NamedKey::ArrowLeft)).as_deref(),
+            Some("ArrowLeft")
+        );
+        assert_eq!(key_name(&Key::Named(NamedKey::Space)).as_deref(), Some(" "));
+        assert_eq!(key_name(&Key::Named(NamedKey::F1)), None);
+        assert_eq!(key_name(&Key::Character("T".into())).as_deref(), Some("t"));
+    }
+
+    #[test]
+    fn default_bindings_are_reachable() {
+        let keymap = Keymap::new();
+        assert_eq!(
+            keymap.action(&Key::Named(NamedKey::PageUp)),
+            Some(Action::PageBack)
+        );
+        assert_eq!(
+            keymap.action(&Key::Named(NamedKey::Home)),
+            Some(Action::JumpToNow)
+        );
+        assert_eq!(
+            keymap.action(&Key::Character("F".into())),
+            Some(Action::Follow)
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use mosaic_model::log::Log;
+
+use crate::{
+    camera::Camera,
+    gpu_data::{GPU_Data, GRID_COLOR, TICK_COLOR},
+    sessions::Log_Kind,
+};
+
+// The lane buffer on the gpu has a fixed size so the bind group never has to be rebuilt
+pub const MAX_LANES: usize = 256;
+pub const LANE_FLOATS: usize = 4; // content center, content height, padding, padding
+
+// Logs that don't name a category all share this lane
+pub const EVENTS_LANE: &str = "Events";
+// Once the lane buffer is full, new categories end up here
+const OVERFLOW_LANE: &str = "Other";
+
+// Lane layout, in pixels before the vertical camera is applied
+const TOP_MARGIN: f64 = 8.0;
+pub const BOTTOM_MARGIN: f64 = 64.0; // room for the ruler
+const HEADER_HEIGHT: f64 = 18.0;
+const MIN_LANE_HEIGHT: f64 = 32.0;
+const MAX_LANE_HEIGHT: f64 = 80.0;
+const HEADER_TEXT_SIZE: f32 = 12.0;
+const HEADER_INDENT: f64 = 8.0;
+// Categories from "category: text" labels longer than this are probably just text with a colon in it
+const MAX_CATEGORY_LENGTH: usize = 32;
+
+// Which lane a log belongs in. Sessions go in a lane named after what they track, other logs can pick
+// a lane with a "category: text" label.
+pub fn lane_name(log: &Log) -> &str {
+    match Log_Kind::of(log) {
+        Log_Kind::Start(label) | Log_Kind::Stop(label) => label,
+        Log_Kind::Event => match log.label.split_once(':') {
+            Some((category, _))
+                if !category.trim().is_empty() && category.len() <= MAX_CATEGORY_LENGTH =>
+            {
+                category.trim()
+            }
+            _ => EVENTS_LANE,
+        },
+    }
+}
+
+pub struct Lane {
+    pub name: String,
+    pub collapsed: bool,
+    pub top: f64,    // pixels from the top of the lane layout, set by layout()
+    pub height: f64, // pixels, including the header
+}
+
+// Horizontal bands stacked down the canvas, one per category. Lane ids are indices into `lanes` and never
+// change once assigned, since they're baked into the rects on the gpu. Display order lives in `order`.
+pub struct Lanes {
+    pub lanes: Vec<Lane>,
+    pub order: Vec<usize>,
+    ids: HashMap<String, usize>,
+    changed: bool, // something other than the camera invalidated the layout
+}
+
+impl Lanes {
+    pub fn new() -> Self {
+        Self {
+            lanes: vec![],
+            order: vec![],
+            ids: HashMap::new(),
+            changed: false,
+        }
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    // The lane for a name, adding a new lane at the bottom the first time it's seen
+    pub fn id_for(&mut self, name: &str) -> usize {
+        if let Some(id) = self.id(name) {
+            return id;
+        }
+
+        let id = if self.lanes.len() >= MAX_LANES - 1 {
+            match self.id(OVERFLOW_LANE) {
+                Some(id) => id,
+                None => self.push(OVERFLOW_LANE),
+            }
+        } else {
+            self.push(name)
+        };
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn push(&mut self, name: &str) -> usize {
+        let id = self.lanes.len();
+        self.lanes.push(Lane {
+            name: name.to_string(),
+            collapsed: false,
+            top: 0.0,
+            height: 0.0,
+        });
+        self.order.push(id);
+        self.ids.insert(name.to_string(), id);
+        self.changed = true;
+        id
+    }
+
+    pub fn set_collapsed(&mut self, id: usize, collapsed: bool) {
+        if let Some(lane) = self.lanes.get_mut(id) {
+            if lane.collapsed != collapsed {
+                lane.collapsed = collapsed;
+                self.changed = true;
+            }
+        }
+    }
+
+    pub fn toggle_collapsed(&mut self, id: usize) {
+        if let Some(lane) = self.lanes.get_mut(id) {
+            lane.collapsed = !lane.collapsed;
+            self.changed = true;
+        }
+    }
+
+    // Moves a lane to a new position in the display order
+    pub fn move_lane(&mut self, id: usize, index: usize) {
+        if let Some(current) = self.order.iter().position(|lane| *lane == id) {
+            self.order.remove(current);
+            self.order.insert(index.min(self.order.len()), id);
+            self.changed = true;
+        }
+    }
+
+    // Expanded lanes share the space between the margins, but never shrink below a readable height.
+    // Returns whether anything moved, in which case the gpu copy needs updating.
+    pub fn layout(&mut self, camera: &Camera) -> bool {
+        let available = camera.dimensions.height as f64 - TOP_MARGIN - BOTTOM_MARGIN;
+        let collapsed = self.lanes.iter().filter(|lane| lane.collapsed).count();
+        let expanded = self.lanes.len() - collapsed;
+        let lane_height = if expanded > 0 {
+            ((available - collapsed as f64 * HEADER_HEIGHT) / expanded as f64)
+                .clamp(MIN_LANE_HEIGHT, MAX_LANE_HEIGHT)
+        } else {
+            0.0
+        };
+
+        let mut moved = std::mem::take(&mut self.changed);
+        let mut top = TOP_MARGIN;
+        for id in &self.order {
+            let lane = &mut self.lanes[*id];
+            let height = if lane.collapsed {
+                HEADER_HEIGHT
+            } else {
+                lane_height
+            };
+            moved |= lane.top != top || lane.height != height;
+            lane.top = top;
+            lane.height = height;
+            top += height;
+        }
+        moved
+    }
+
+    // Where the last lane ends
+    pub fn content_height(&self) -> f64 {
+        self.order
+            .last()
+            .map(|id| self.lanes[*id].top + self.lanes[*id].height)
+            .unwrap_or(0.0)
+    }
+
+    // How much of the canvas the lanes get, the ruler has the rest
+    pub fn visible_height(camera: &Camera) -> f64 {
+        (camera.dimensions.height as f64 - BOTTOM_MARGIN).max(0.0)
+    }
+
+    // Where each lane's rects go, in screen space before the vertical camera is applied, indexed by lane id
+    pub fn gpu_layout(&self, camera: &Camera) -> Vec<f32> {
+        let height = camera.dimensions.height as f64;
+        let mut layout = Vec::with_capacity(self.lanes.len() * LANE_FLOATS);
+        for lane in &self.lanes {
+            let content_top = lane.top + HEADER_HEIGHT;
+            let content_height = if lane.collapsed {
+                0.0
+            } else {
+                lane.height - HEADER_HEIGHT
+            };
+            let center = 1.0 - (content_top + content_height / 2.0) / height * 2.0;
+            layout.extend_from_slice(&[
+                center as f32,
+                (content_height / height * 2.0) as f32,
+                0.0,
+                0.0,
+            ]);
+        }
+        layout
+    }
+
+    // The lane under a vertical pixel position on the canvas, and whether the position is on its header
+    pub fn lane_at(&self, y: f64, camera: &Camera) -> Option<(usize, bool)> {
+        if y >= Self::visible_height(camera) {
+            return None;
+        }
+        let y = camera.screen_to_content_y(y);
+        self.order.iter().find_map(|id| {
+            let lane = &self.lanes[*id];
+            if lane.top <= y && y < lane.top + lane.height {
+                Some((*id, y < lane.top + HEADER_HEIGHT))
+            } else {
+                None
+            }
+        })
+    }
+
+    // Lane names and the lines between lanes
+    pub fn headers_gpu_data(&self, camera: &Camera) -> Vec<GPU_Data> {
+        let mut data = vec![];
+        if camera.dimensions.width == 0 || camera.dimensions.height == 0 {
+            return data;
+        }
+        let (width, height) = (
+            camera.dimensions.width as f64,
+            camera.dimensions.height as f64,
+        );
+
+        let to_ndc = |y: f64| 1.0 - camera.content_to_screen_y(y) / height * 2.0;
+        let visible_height = Self::visible_height(camera);
+        for id in &self.order {
+            let lane = &self.lanes[*id];
+            let (top, bottom) = (
+                camera.content_to_screen_y(lane.top),
+                camera.content_to_screen_y(lane.top + lane.height),
+            );
+            if bottom < 0.0 || top > visible_height {
+                continue;
+            }
+            // headers are fixed rects, so unlike the lanes they aren't clipped at the ruler by the shader
+            if bottom <= visible_height {
+                data.push(GPU_Data::Rect {
+                    x: 0.0,
+                    y: to_ndc(lane.top + lane.height) as f32,
+                    w: 2.0,
+                    h: (2.0 / height) as f32,
+                    color_index: GRID_COLOR,
+                    fixed: 1.0,
+                });
+            }
+
+            // names would spill out of headers squashed by the vertical zoom, or into the ruler
+            if HEADER_HEIGHT * camera.y_scale < HEADER_TEXT_SIZE as f64
+                || top + HEADER_HEIGHT * camera.y_scale > visible_height
+            {
+                continue;
+            }
+            let marker = if lane.collapsed { "+" } else { "-" };
+            let baseline = 1.0 - (top + HEADER_HEIGHT * camera.y_scale - 5.0) / height * 2.0;
+            data.push(GPU_Data::Text {
+                text: format!("{} {}", marker, lane.name),
+                x: (HEADER_INDENT / width * 2.0 - 1.0) as f32,
+                y: baseline as f32,
+                size: HEADER_TEXT_SIZE,
+                align: 0.0,
+                color_index: TICK_COLOR,
+                fixed: 1.0,
+            });
+        }
+
+        data
+    }
+}
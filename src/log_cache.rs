@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use js_sys::{Array, Function, Object, Reflect};
+use plinth_util::{logging::log, time::now};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{IdbDatabase, IdbObjectStoreParameters, IdbOpenDbRequest, IdbTransactionMode};
+
+use crate::{
+    global_app::app_mut,
+    log_parser::parse_body,
+    range_loader::{trim_cached, Range_Set},
+};
+
+const DB_NAME: &str = "mosaic-hub";
+const DB_VERSION: u32 = 1;
+const STORE: &str = "logs";
+// Every fetch and stream batch is its own record, past this many they're merged into one
+const MAX_RECORDS: u32 = 64;
+// Older logs are dropped from the cache at startup, and the oldest go first if there's more than MAX_BYTES
+const MAX_AGE_MS: f64 = 30.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+const MAX_BYTES: usize = 32 * 1024 * 1024;
+
+// Logs kept in the browser's IndexedDB so they show up at startup before the network answers, and so only what's
+// missing needs fetching. Each record is the raw body of logs as it arrived, with the time ranges it is
+// complete for (none for logs from the stream, which only cover whatever happened to be sent).
+pub struct Log_Cache {
+    db: Option<IdbDatabase>,
+    records: u32, // records in the store, as far as we know
+}
+
+impl Log_Cache {
+    pub fn new() -> Self {
+        Self {
+            db: None,
+            records: 0,
+        }
+    }
+
+    // Saves a body of logs, complete from start to end for each of ranges. Fire and forget, the timeline works
+    // the same whether it makes it into the cache or not.
+    // The stream saves a batch every frame while logs are coming in, so past MAX_RECORDS everything is merged
+    // into one record again.
+    pub fn save(&mut self, ranges: &[(f64, f64)], body: &str) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        if body.trim().is_empty() && ranges.is_empty() {
+            return;
+        }
+        let record = record(ranges, body);
+        let result = db
+            .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)
+            .and_then(|transaction| transaction.object_store(STORE))
+            .and_then(|store| store.add(&record));
+        if result.is_err() {
+            log("Failed to save logs to the cache. Fn: Log_Cache::save()");
+            return;
+        }
+        self.records += 1;
+        if self.records > MAX_RECORDS {
+            merge_records(db);
+            self.records = 1;
+        }
+    }
+}
+
+// Opens the cache and feeds everything in it to the app, then calls on_ready. on_ready is called even if the
+// cache can't be opened (like in a private window), there's just nothing in it then.
+pub fn open(on_ready: Function) {
+    let Some(factory) = web_sys::window().and_then(|window| window.indexed_db().ok().flatten())
+    else {
+        ready(&on_ready);
+        return;
+    };
+    let Ok(request) = factory.open_with_u32(DB_NAME, DB_VERSION) else {
+        ready(&on_ready);
+        return;
+    };
+
+    // first visit, or the layout changed
+    let upgrade_request = request.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        let Some(db) = database(&upgrade_request) else {
+            return;
+        };
+        if !db.object_store_names().contains(STORE) {
+            let parameters = IdbObjectStoreParameters::new();
+            parameters.set_auto_increment(true);
+            if db
+                .create_object_store_with_optional_parameters(STORE, &parameters)
+                .is_err()
+            {
+                log("Failed to create the log cache. Fn: log_cache::open()");
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    let error_ready = on_ready.clone();
+    let on_error = Closure::once_into_js(move || {
+        log("Failed to open the log cache. Fn: log_cache::open()");
+        ready(&error_ready);
+    });
+    request.set_onerror(Some(on_error.unchecked_ref()));
+
+    let success_request = request.clone();
+    let on_success = Closure::once_into_js(move || match database(&success_request) {
+        Some(db) => load(db, on_ready),
+        None => ready(&on_ready),
+    });
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+}
+
+fn ready(on_ready: &Function) {
+    if on_ready.call0(&JsValue::NULL).is_err() {
+        log("Cache ready callback threw an error. Fn: log_cache::open()");
+    }
+}
+
+fn database(request: &IdbOpenDbRequest) -> Option<IdbDatabase> {
+    request.result().ok()?.dyn_into::<IdbDatabase>().ok()
+}
+
+// Reads every record into the app, then keeps the database around for saving
+fn load(db: IdbDatabase, on_ready: Function) {
+    let request = match db
+        .transaction_with_str(STORE)
+        .and_then(|transaction| transaction.object_store(STORE))
+        .and_then(|store| store.get_all())
+    {
+        Ok(request) => request,
+        Err(_) => {
+            app_mut().unwrap().log_cache.db = Some(db);
+            ready(&on_ready);
+            return;
+        }
+    };
+
+    let all_request = request.clone();
+    let on_success = Closure::once_into_js(move || {
+        let records: Array = all_request
+            .result()
+            .ok()
+            .and_then(|result| result.dyn_into().ok())
+            .unwrap_or_default();
+
+        let (mut ranges, lines) = merge(&records);
+        let parsed = parse_body(lines.join("\n").as_bytes(), now());
+        let mut app = app_mut().unwrap();
+        app.report_parse_errors("the cache", &parsed);
+        let (logs, body, trimmed) = trim_cached(
+            parsed.logs,
+            &parsed.accepted,
+            &mut ranges,
+            now() - MAX_AGE_MS,
+            MAX_BYTES,
+        );
+        app.log_cache.records = records.length();
+        if trimmed || records.length() > MAX_RECORDS {
+            compact(&db, &ranges.ranges, &body);
+            app.log_cache.records = 1;
+        }
+        app.log_cache.db = Some(db);
+
+        // ranges first, so sessions can tell whether their stop could be missing. Logs may have arrived at the
+        // backend late, after the newest of them was cached, so the time after it is fetched again. Without any
+        // logs, the cached ranges are all there is to go on.
+        for &(start, end) in &ranges.ranges {
+            app.range_loader.mark_loaded(start, end);
+        }
+        let last = logs.iter().map(|log| log.timestamp as f64).reduce(f64::max);
+        if let Some(last) = last.or(ranges.ranges.last().map(|(_, end)| *end)) {
+            app.range_loader.forget_after(last);
+        }
+        app.receive_logs(logs);
+        drop(app);
+        ready(&on_ready);
+    });
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+}
+
+// Every range the records are complete for, and every distinct line in them
+fn merge(records: &Array) -> (Range_Set, Vec<String>) {
+    let mut ranges = Range_Set::new();
+    let mut lines = vec![];
+    let mut seen = HashSet::new();
+    for record in records.iter() {
+        let (record_ranges, body) = parse_record(&record);
+        for (start, end) in record_ranges {
+            ranges.insert(start, end);
+        }
+        // records overlap wherever a range was fetched again, no need to keep duplicates around
+        for line in body.lines() {
+            if seen.insert(line.to_string()) {
+                lines.push(line.to_string());
+            }
+        }
+    }
+    (ranges, lines)
+}
+
+// Reads the records back and replaces them with one holding all of them. It all happens in one transaction, so a
+// record saved in the meantime waits for it rather than getting lost.
+fn merge_records(db: &IdbDatabase) {
+    let Ok(store) = db
+        .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)
+        .and_then(|transaction| transaction.object_store(STORE))
+    else {
+        log("Failed to compact the log cache. Fn: log_cache::merge_records()");
+        return;
+    };
+    let Ok(request) = store.get_all() else {
+        log("Failed to compact the log cache. Fn: log_cache::merge_records()");
+        return;
+    };
+
+    let all_request = request.clone();
+    let on_success = Closure::once_into_js(move || {
+        let records: Array = all_request
+            .result()
+            .ok()
+            .and_then(|result| result.dyn_into().ok())
+            .unwrap_or_default();
+        let (ranges, lines) = merge(&records);
+        let result = store
+            .clear()
+            .and_then(|_| store.add(&record(&ranges.ranges, &lines.join("\n"))));
+        if result.is_err() {
+            log("Failed to compact the log cache. Fn: log_cache::merge_records()");
+        }
+    });
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+}
+
+// Replaces every record with a single one holding all of them
+fn compact(db: &IdbDatabase, ranges: &[(f64, f64)], body: &str) {
+    let result = db
+        .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)
+        .and_then(|transaction| transaction.object_store(STORE))
+        .and_then(|store| {
+            store.clear()?;
+            store.add(&record(ranges, body))
+        });
+    if result.is_err() {
+        log("Failed to compact the log cache. Fn: log_cache::compact()");
+    }
+}
+
+// { ranges: [start, end, start, end, ...], body }
+fn record(ranges: &[(f64, f64)], body: &str) -> JsValue {
+    let flat: Array = ranges
+        .iter()
+        .flat_map(|(start, end)| [JsValue::from_f64(*start), JsValue::from_f64(*end)])
+        .collect();
+    let object = Object::new();
+    let _ = Reflect::set(&object, &JsValue::from_str("ranges"), &flat);
+    let _ = Reflect::set(
+        &object,
+        &JsValue::from_str("body"),
+        &JsValue::from_str(body),
+    );
+    object.into()
+}
+
+fn parse_record(record: &JsValue) -> (Vec<(f64, f64)>, String) {
+    let flat: Vec<f64> = Reflect::get(record, &JsValue::from_str("ranges"))
+        .ok()
+        .and_then(|ranges| ranges.dyn_into::<Array>().ok())
+        .map(|ranges| ranges.iter().filter_map(|value| value.as_f64()).collect())
+        .unwrap_or_default();
+    let ranges = flat
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect();
+    let body = Reflect::get(record, &JsValue::from_str("body"))
+        .ok()
+        .and_then(|body| body.as_string())
+        .unwrap_or_default();
+    (ranges, body)
+}
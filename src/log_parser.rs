@@ -0,0 +1,202 @@
+use mosaic_model::log::Log;
+
+// Raw text kept in a report is cut down to this many characters, a whole garbage body could be one long line
+const MAX_ERROR_TEXT: usize = 200;
+// Only this many errors are kept per body, the count still includes the rest
+const MAX_REPORTED_ERRORS: usize = 100;
+// Logs from further in the future than this are assumed to be garbage rather than clock skew
+const MAX_FUTURE_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+// A line that couldn't be turned into a log. line counts from 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parse_Error {
+    pub line: usize,
+    pub reason: String,
+    pub text: String,
+}
+
+pub struct Parsed_Body {
+    pub logs: Vec<Log>,
+    pub accepted: String, // the lines that became logs, what's worth caching
+    pub lines: usize,     // non blank lines in the body
+    pub error_count: usize,
+    pub errors: Vec<Parse_Error>, // the first MAX_REPORTED_ERRORS of them
+}
+
+// Turns a body of logs, one per line, into logs. Lines that aren't valid are skipped and reported, the rest of
+// the body still goes through. now is the current time in ms, logs too far past it are rejected.
+pub fn parse_body(body: &[u8], now: f64) -> Parsed_Body {
+    let mut parsed = Parsed_Body {
+        logs: vec![],
+        accepted: String::new(),
+        lines: 0,
+        error_count: 0,
+        errors: vec![],
+    };
+    for (index, bytes) in body.split(|byte| *byte == b'\n').enumerate() {
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        if bytes.iter().all(|byte| byte.is_ascii_whitespace()) {
+            continue;
+        }
+        parsed.lines += 1;
+        match parse_line(bytes, now) {
+            Ok(log) => {
+                let line = std::str::from_utf8(bytes).unwrap();
+                if !parsed.accepted.is_empty() {
+                    parsed.accepted.push('\n');
+                }
+                parsed.accepted.push_str(line);
+                parsed.logs.push(log);
+            }
+            Err(reason) => {
+                parsed.error_count += 1;
+                if parsed.errors.len() < MAX_REPORTED_ERRORS {
+                    parsed.errors.push(Parse_Error {
+                        line: index + 1,
+                        reason,
+                        text: String::from_utf8_lossy(bytes)
+                            .chars()
+                            .take(MAX_ERROR_TEXT)
+                            .collect(),
+                    });
+                }
+            }
+        }
+    }
+    parsed
+}
+
+// A line is "label,timestamp"
+fn parse_line(bytes: &[u8], now: f64) -> Result<Log, String> {
+    let line = std::str::from_utf8(bytes)
+        .map_err(|e| format!("invalid UTF-8 at byte {}", e.valid_up_to()))?;
+    if line.chars().any(|c| c.is_control() && c != '\t') {
+        return Err("contains control characters".to_string());
+    }
+
+    let (label, timestamp) = line.split_once(',').ok_or("missing timestamp")?;
+    let timestamp = timestamp
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| format!("timestamp {:?} is not a number", timestamp.trim()))?;
+    if timestamp <= 0 || timestamp as f64 > now + MAX_FUTURE_MS {
+        return Err(format!("timestamp {} is out of range", timestamp));
+    }
+    if label.trim().is_empty() {
+        return Err("missing label".to_string());
+    }
+    Ok(Log {
+        label: label.to_string(),
+        timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: f64 = 1_700_000_000_000.0;
+
+    fn parse(body: &str) -> Parsed_Body {
+        parse_body(body.as_bytes(), NOW)
+    }
+
+    fn reasons(parsed: &Parsed_Body) -> Vec<(usize, &str)> {
+        parsed
+            .errors
+            .iter()
+            .map(|error| (error.line, error.reason.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_good_lines() {
+        let parsed = parse("start build,1699999999000\nbuild: step 2,1699999999500");
+        let logs: Vec<(&str, i64)> = parsed
+            .logs
+            .iter()
+            .map(|log| (log.label.as_str(), log.timestamp))
+            .collect();
+        assert_eq!(
+            logs,
+            vec![
+                ("start build", 1699999999000),
+                ("build: step 2", 1699999999500)
+            ]
+        );
+        assert_eq!(parsed.lines, 2);
+        assert_eq!(parsed.error_count, 0);
+    }
+
+    #[test]
+    fn rejects_lines_without_a_comma() {
+        let parsed = parse("no timestamp here");
+        assert!(parsed.logs.is_empty());
+        assert_eq!(reasons(&parsed), vec![(1, "missing timestamp")]);
+        assert_eq!(parsed.errors[0].text, "no timestamp here");
+    }
+
+    #[test]
+    fn rejects_timestamps_that_arent_numbers() {
+        let parsed = parse("a,soon\nb,12.5\nc,\nd,1,2");
+        assert!(parsed.logs.is_empty());
+        assert_eq!(parsed.error_count, 4);
+        assert!(parsed
+            .errors
+            .iter()
+            .all(|error| error.reason.ends_with("is not a number")));
+    }
+
+    #[test]
+    fn rejects_timestamps_out_of_range() {
+        let parsed = parse("a,0\nb,-5\nc,99999999999999999");
+        assert!(parsed.logs.is_empty());
+        assert_eq!(parsed.error_count, 3);
+        assert!(parsed.errors[0].reason.ends_with("is out of range"));
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        let mut body = b"good,1699999999000\n".to_vec();
+        body.extend_from_slice(b"bad \xff\xfe,1699999999000");
+        let parsed = parse_body(&body, NOW);
+        assert_eq!(parsed.logs.len(), 1);
+        assert_eq!(reasons(&parsed), vec![(2, "invalid UTF-8 at byte 4")]);
+        // the report still shows what it could
+        assert!(parsed.errors[0].text.starts_with("bad "));
+    }
+
+    #[test]
+    fn handles_crlf_and_blank_lines() {
+        let parsed = parse("a,1699999999000\r\n\r\n   \nb,1699999999001\r\n");
+        assert_eq!(parsed.logs.len(), 2);
+        assert_eq!(parsed.logs[1].label, "b");
+        assert_eq!(parsed.lines, 2);
+        assert_eq!(parsed.accepted, "a,1699999999000\nb,1699999999001");
+    }
+
+    #[test]
+    fn line_numbers_count_blank_lines() {
+        let parsed = parse("a,1699999999000\n\n\nbroken\n\nalso broken");
+        assert_eq!(
+            reasons(&parsed),
+            vec![(4, "missing timestamp"), (6, "missing timestamp")]
+        );
+    }
+
+    #[test]
+    fn keeps_counting_past_the_reported_errors() {
+        let body = vec!["broken"; MAX_REPORTED_ERRORS + 10].join("\n");
+        let parsed = parse(&body);
+        assert_eq!(parsed.error_count, MAX_REPORTED_ERRORS + 10);
+        assert_eq!(parsed.errors.len(), MAX_REPORTED_ERRORS);
+        assert_eq!(parsed.errors.last().unwrap().line, MAX_REPORTED_ERRORS);
+    }
+
+    #[test]
+    fn accepted_holds_only_good_lines() {
+        let parsed = parse("a,1699999999000\nbroken\n,1699999999000\nb,1699999999001\nc,x");
+        assert_eq!(parsed.accepted, "a,1699999999000\nb,1699999999001");
+        assert_eq!(parsed.error_count, 3);
+    }
+}
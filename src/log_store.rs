@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+
+use mosaic_model::log::Log;
+
+// Two logs with the same timestamp and label are the same log, no matter how many times they're fetched
+pub type Log_Key = (i64, String);
+
+pub fn log_key(log: &Log) -> Log_Key {
+    (log.timestamp, log.label.clone())
+}
+
+// Logs ordered by time, so finding what's on screen or near the cursor doesn't mean scanning everything.
+pub struct Log_Store {
+    entries: BTreeMap<Log_Key, Log>,
+}
+
+impl Log_Store {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Adds a log unless we already have it, returns whether it was new
+    pub fn insert(&mut self, log: Log) -> bool {
+        let key = log_key(&log);
+        if self.entries.contains_key(&key) {
+            return false;
+        }
+        self.entries.insert(key, log);
+        true
+    }
+
+    pub fn remove(&mut self, timestamp: i64, label: &str) -> Option<Log> {
+        self.entries.remove(&(timestamp, label.to_string()))
+    }
+
+    pub fn get(&self, timestamp: i64, label: &str) -> Option<&Log> {
+        self.entries.get(&(timestamp, label.to_string()))
+    }
+
+    pub fn contains(&self, timestamp: i64, label: &str) -> bool {
+        self.entries.contains_key(&(timestamp, label.to_string()))
+    }
+
+    // Every log in time order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Log> {
+        self.entries.values()
+    }
+
+    // Logs with start_ms <= timestamp < end_ms, in time order
+    pub fn range(&self, start_ms: i64, end_ms: i64) -> impl DoubleEndedIterator<Item = &Log> {
+        // BTreeMap::range panics on backwards ranges, an inverted range is just empty
+        let end_ms = end_ms.max(start_ms);
+        // the empty string sorts before every other label, so these bounds cover whole milliseconds
+        self.entries
+            .range((
+                Included((start_ms, String::new())),
+                Excluded((end_ms, String::new())),
+            ))
+            .map(|(_, log)| log)
+    }
+
+    // The log closest in time to the timestamp, ties go to the earlier log
+    pub fn nearest(&self, timestamp: i64) -> Option<&Log> {
+        let before = self
+            .entries
+            .range((Unbounded, Excluded((timestamp, String::new()))))
+            .next_back()
+            .map(|(_, log)| log);
+        let after = self
+            .entries
+            .range((Included((timestamp, String::new())), Unbounded))
+            .next()
+            .map(|(_, log)| log);
+
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                if timestamp - before.timestamp <= after.timestamp - timestamp {
+                    Some(before)
+                } else {
+                    Some(after)
+                }
+            }
+            (before, after) => before.or(after),
+        }
+    }
+
+    pub fn first(&self) -> Option<&Log> {
+        self.entries.values().next()
+    }
+
+    pub fn last(&self) -> Option<&Log> {
+        self.entries.values().next_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(timestamp: i64, label: &str) -> Log {
+        Log {
+            label: label.to_string(),
+            timestamp,
+        }
+    }
+
+    fn store(entries: &[(i64, &str)]) -> Log_Store {
+        let mut store = Log_Store::new();
+        for (timestamp, label) in entries {
+            store.insert(log(*timestamp, label));
+        }
+        store
+    }
+
+    fn timestamps<'a>(logs: impl Iterator<Item = &'a Log>) -> Vec<i64> {
+        logs.map(|log| log.timestamp).collect()
+    }
+
+    #[test]
+    fn iterates_in_time_order() {
+        let store = store(&[(30, "c"), (10, "a"), (20, "b")]);
+        assert_eq!(timestamps(store.iter()), vec![10, 20, 30]);
+        assert_eq!(store.first().unwrap().timestamp, 10);
+        assert_eq!(store.last().unwrap().timestamp, 30);
+    }
+
+    #[test]
+    fn insert_skips_duplicates() {
+        let mut store = store(&[(10, "a")]);
+        assert!(!store.insert(log(10, "a")));
+        assert!(store.insert(log(10, "b")));
+        assert!(store.insert(log(11, "a")));
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn range_is_half_open() {
+        let store = store(&[(10, "a"), (20, "a"), (20, "b"), (30, "a"), (40, "a")]);
+        assert_eq!(timestamps(store.range(20, 40)), vec![20, 20, 30]);
+        assert_eq!(timestamps(store.range(0, 11)), vec![10]);
+        assert_eq!(timestamps(store.range(41, 100)), Vec::<i64>::new());
+        assert_eq!(timestamps(store.range(30, 30)), Vec::<i64>::new());
+        assert_eq!(timestamps(store.range(30, 10)), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn nearest_picks_the_closest_log() {
+        let store = store(&[(10, "a"), (20, "a"), (40, "a")]);
+        assert_eq!(store.nearest(0).unwrap().timestamp, 10);
+        assert_eq!(store.nearest(14).unwrap().timestamp, 10);
+        assert_eq!(store.nearest(15).unwrap().timestamp, 10);
+        assert_eq!(store.nearest(16).unwrap().timestamp, 20);
+        assert_eq!(store.nearest(20).unwrap().timestamp, 20);
+        assert_eq!(store.nearest(100).unwrap().timestamp, 40);
+        assert!(Log_Store::new().nearest(0).is_none());
+    }
+
+    #[test]
+    fn remove_takes_the_log_out() {
+        let mut store = store(&[(10, "a"), (10, "b")]);
+        assert_eq!(store.remove(10, "a").unwrap().label, "a");
+        assert!(store.remove(10, "a").is_none());
+        assert!(!store.contains(10, "a"));
+        assert!(store.contains(10, "b"));
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -5,15 +5,29 @@ use plinth_core::graphics::Rc;
 use std::cell::RefCell;
 
 mod camera;
+mod camera_animation;
 mod data;
+mod density;
 mod global_app;
+mod glyph_atlas;
 mod gpu_data;
 mod gpu_resources;
 mod input_manager;
 mod io;
+mod keymap;
+mod lanes;
+mod log_cache;
+mod log_parser;
+mod log_store;
 mod my_app;
+mod picking;
 mod plinth_app;
 mod plinth_rendering;
+mod range_loader;
+mod ruler;
+mod sessions;
+mod sorted_rects;
+mod view_state;
 
 pub fn main() {
     let user_app = Rc::new(RefCell::new(MyApp::new()));
@@ -1,18 +1,59 @@
+use std::collections::HashSet;
+
 use js_sys::Uint8Array;
 use mosaic_model::log::Log;
+use plinth_util::{logging::log, time::now};
+use wasm_bindgen::JsValue;
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    camera::Camera, data::Data, gpu_data::GPU_Data, gpu_resources::GPU_Resources,
+    camera::{Camera, View},
+    data::Data,
+    density::Density,
+    gpu_data::GPU_Data,
+    gpu_resources::GPU_Resources,
     input_manager::Input_State,
+    io::{parse_report_to_js, selection_to_js},
+    keymap::Keymap,
+    lanes::{lane_name, Lanes},
+    log_cache::Log_Cache,
+    log_parser::Parsed_Body,
+    log_store::Log_Key,
+    picking::{highlight_gpu_data, pick, pick_log, selection_gpu_data, tooltip_gpu_data, Pick},
+    range_loader::Range_Loader,
+    ruler::Ruler,
+    sessions::{Log_Kind, Sessions},
+    view_state::View_State,
 };
 
+// How long the view has to sit still before the url is updated, browsers limit how often it can change
+const VIEW_STATE_DEBOUNCE_MS: f64 = 500.0;
+
 pub struct MyApp {
     pub data: Data,
     pub gpu_resources: GPU_Resources,
     pub frame_start: f64,
+    pub last_frame: f64, // when before_render last ran, for anything animated
     pub camera: Camera,
     pub input_state: Input_State,
+    pub keymap: Keymap,
+    pub ruler: Ruler,
+    pub last_view: Option<View>,
+    pub last_overlay_refresh: f64,
+    pub overlay_dirty: bool, // set when something other than the camera needs the overlay rebuilt
+    pub selection_callback: Option<js_sys::Function>, // registered by the page to show the selected log
+    pub view_state_callback: Option<js_sys::Function>, // registered by the page to put the view in the url
+    pub view_state: String,                            // the latest encoded View_State
+    pub view_state_sent: String,                       // the last one the page was told about
+    pub view_state_changed: f64,                       // when view_state last changed
+    pub pending_selection: Option<Log_Key>, // restored selection, waiting for its log to be loaded
+    pub pending_collapsed: Vec<String>, // restored collapsed lanes, waiting for their logs to be loaded
+    pub follow_callback: Option<js_sys::Function>, // registered by the page to show whether the view follows now
+    pub following_sent: bool, // the last following state the page was told about
+    pub range_loader: Range_Loader,
+    pub log_cache: Log_Cache,
+    pub parse_error_callback: Option<js_sys::Function>, // registered by the page to show lines that were skipped
+    pub range_request_callback: Option<js_sys::Function>, // registered by the page to fetch a time range of logs
 }
 
 impl MyApp {
@@ -21,21 +62,313 @@ impl MyApp {
             data: Data::new(),
             gpu_resources: GPU_Resources::new(),
             frame_start: 0.0,
+            last_frame: now(),
             camera: Camera::new(),
             input_state: Input_State::new(),
+            keymap: Keymap::new(),
+            ruler: Ruler::new(),
+            last_view: None,
+            last_overlay_refresh: 0.0,
+            overlay_dirty: false,
+            selection_callback: None,
+            view_state_callback: None,
+            view_state: String::new(),
+            view_state_sent: String::new(),
+            view_state_changed: 0.0,
+            pending_selection: None,
+            pending_collapsed: vec![],
+            follow_callback: None,
+            following_sent: false,
+            range_loader: Range_Loader::new(),
+            log_cache: Log_Cache::new(),
+            parse_error_callback: None,
+            range_request_callback: None,
+        }
+    }
+
+    // Only logs we haven't seen before are queued for the gpu, so fetching overlapping ranges is harmless. Returns
+    // whether any of the logs were new.
+    pub fn receive_logs(&mut self, logs: Vec<Log>) -> bool {
+        let mut received = false;
+        for log in logs {
+            if self.data.logs.contains(log.timestamp, &log.label) {
+                continue;
+            }
+            // "start X" and "stop X" logs are drawn as one span covering the time between them,
+            // everything else is an instant
+            let lane = self.data.lanes.id_for(lane_name(&log));
+            let point = match Log_Kind::of(&log) {
+                Log_Kind::Event => Some(GPU_Data::from_log(&log, lane)),
+                _ => None,
+            };
+            received = true;
+            self.data.density.insert(lane, log.timestamp);
+            self.data.sessions.insert(&log);
+            if let Some(point) = point {
+                self.data.queue.push_back(point);
+            }
+            self.data.logs.insert(log);
+        }
+        if !received {
+            return false;
         }
+
+        self.update_sessions();
+        self.apply_pending_view_state();
+        self.refresh_overlay();
+        true
     }
 
-    pub fn receive_logs(&mut self, body: String) {
-        for log in body.lines() {
-            self.data.logs.push(Log::from_http_body(log.to_string()));
+    // The logs for a whole time range. Even without new logs in it, the range can tell sessions around it that
+    // there's no stop they're missing.
+    pub fn receive_range(&mut self, logs: Vec<Log>, start: f64, end: f64) {
+        self.range_loader.loaded(start, end);
+        if !self.receive_logs(logs) {
+            self.update_sessions();
+            self.refresh_overlay();
         }
+    }
 
-        for log in &self.data.logs {
+    // Pairs the starts and stops that arrived, and brings the session spans in the rect buffer in line with the
+    // result. Spans are replaced when their session changes, like an orphan stop whose start just loaded.
+    pub fn update_sessions(&mut self) {
+        let now = now();
+        let range_loader = &self.range_loader;
+        let (removed, added) = self.data.sessions.update(|start| {
+            range_loader
+                .gap_after(start as f64, now)
+                .map(|gap| gap as i64)
+        });
+        for (start, end, label) in removed {
+            let lane = self.data.lanes.id_for(&label);
             self.data
                 .queue
-                .push_back(GPU_Data::from_log(&log, &self.camera));
+                .push_back(GPU_Data::RemoveSpan { start, end, lane });
+        }
+        for (start, end, label) in added {
+            let lane = self.data.lanes.id_for(&label);
+            self.data
+                .queue
+                .push_back(GPU_Data::Span { start, end, lane });
+        }
+    }
+
+    // Selects a log or session (None clears the selection) and tells the page about it
+    pub fn select(&mut self, selected: Option<Pick>) {
+        if self.input_state.selected == selected {
+            return;
+        }
+        self.input_state.selected = selected;
+        self.overlay_dirty = true;
+
+        if let Some(callback) = &self.selection_callback {
+            let value = selection_to_js(self.input_state.selected.as_ref(), &self.data);
+            if let Err(e) = callback.call1(&JsValue::NULL, &value) {
+                log("Selection callback threw an error. Fn: select()");
+            }
+        }
+    }
+
+    pub fn current_view_state(&self) -> View_State {
+        let lanes = &self.data.lanes;
+        View_State {
+            pos: self.camera.pos,
+            scale: self.camera.scale,
+            selected: self
+                .input_state
+                .selected
+                .as_ref()
+                .map(|selected| selected.log.clone()),
+            collapsed: lanes
+                .order
+                .iter()
+                .map(|id| &lanes.lanes[*id])
+                .filter(|lane| lane.collapsed)
+                .map(|lane| lane.name.clone())
+                .collect(),
+        }
+    }
+
+    // Moves to a view from a link. The selection and lanes may not have been loaded yet, so they're applied
+    // as their logs show up.
+    pub fn restore_view_state(&mut self, state: View_State) {
+        self.camera.restore(state.pos, state.scale);
+        for id in 0..self.data.lanes.lanes.len() {
+            let collapsed = state.collapsed.contains(&self.data.lanes.lanes[id].name);
+            self.data.lanes.set_collapsed(id, collapsed);
+        }
+        self.pending_collapsed = state.collapsed;
+        self.pending_selection = state.selected;
+        if self.pending_selection.is_none() {
+            self.select(None);
+        }
+        self.apply_pending_view_state();
+        self.overlay_dirty = true;
+    }
+
+    fn apply_pending_view_state(&mut self) {
+        let lanes = &mut self.data.lanes;
+        self.pending_collapsed.retain(|name| match lanes.id(name) {
+            Some(id) => {
+                lanes.set_collapsed(id, true);
+                false
+            }
+            None => true,
+        });
+
+        if let Some(key) = &self.pending_selection {
+            if let Some(picked) = pick_log(&self.data, key, now() as i64) {
+                self.pending_selection = None;
+                self.select(Some(picked));
+            }
+        }
+    }
+
+    // Tells the page about the view once it has settled, so the url always links back to it
+    pub fn sync_view_state(&mut self) {
+        let state = self.current_view_state().encode();
+        let time = now();
+        if state != self.view_state {
+            self.view_state = state;
+            self.view_state_changed = time;
+        }
+        if self.view_state == self.view_state_sent
+            || time - self.view_state_changed < VIEW_STATE_DEBOUNCE_MS
+        {
+            return;
+        }
+
+        self.view_state_sent = self.view_state.clone();
+        if let Some(callback) = &self.view_state_callback {
+            if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from_str(&self.view_state)) {
+                log("View state callback threw an error. Fn: sync_view_state()");
+            }
+        }
+    }
+
+    // Logs the lines of a body that couldn't be read, and passes them on to the page. source says where the
+    // body came from.
+    pub fn report_parse_errors(&self, source: &str, parsed: &Parsed_Body) {
+        if parsed.error_count == 0 {
+            return;
+        }
+        log(format!(
+            "Skipped {} of {} lines from {}",
+            parsed.error_count, parsed.lines, source
+        )
+        .as_str());
+        for error in &parsed.errors {
+            log(format!("  line {}: {}: {}", error.line, error.reason, error.text).as_str());
         }
+        if let Some(callback) = &self.parse_error_callback {
+            if let Err(e) = callback.call1(&JsValue::NULL, &parse_report_to_js(source, parsed)) {
+                log("Parse error callback threw an error. Fn: report_parse_errors()");
+            }
+        }
+    }
+
+    // Tells the page when following starts or stops, panning away stops it without the page knowing
+    pub fn sync_following(&mut self) {
+        if self.camera.following == self.following_sent {
+            return;
+        }
+        self.following_sent = self.camera.following;
+        if let Some(callback) = &self.follow_callback {
+            if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from_bool(self.following_sent))
+            {
+                log("Follow callback threw an error. Fn: sync_following()");
+            }
+        }
+    }
+
+    // Asks the page for whatever the view is about to need that hasn't been loaded yet
+    pub fn request_ranges(&mut self) {
+        // the view can wander a little past the loaded time, but springs back to it. Now is always in bounds.
+        let first_log = self.data.logs.first().map(|log| log.timestamp as f64);
+        self.camera.bounds = self.range_loader.bounds(&self.camera, now(), first_log);
+        let Some(callback) = &self.range_request_callback else {
+            return;
+        };
+        for (start, end) in self.range_loader.update(&self.camera, now()) {
+            let (start, end) = (
+                JsValue::from_f64(start.floor()),
+                JsValue::from_f64(end.ceil()),
+            );
+            if let Err(e) = callback.call2(&JsValue::NULL, &start, &end) {
+                log("Range request callback threw an error. Fn: request_ranges()");
+            }
+        }
+    }
+
+    // Everything that's been loaded, with running sessions reaching to now
+    pub fn loaded_range(&self) -> Option<(f64, f64)> {
+        let first = self.data.logs.first()?.timestamp as f64;
+        let mut last = self.data.logs.last()?.timestamp as f64;
+        if self.data.sessions.running().next().is_some() {
+            last = last.max(now());
+        }
+        Some((first, last))
+    }
+
+    // Rebuild everything that depends on where the camera is looking
+    pub fn refresh_overlay(&mut self) {
+        if self.data.lanes.layout(&self.camera) {
+            self.data.lane_layout = Some(self.data.lanes.gpu_layout(&self.camera));
+            // lanes collapsing or the canvas growing can leave the lane area scrolled past its end
+            self.camera.clamp_y(
+                self.data.lanes.content_height(),
+                Lanes::visible_height(&self.camera),
+            );
+        }
+        self.ruler.update(&self.camera);
+        let mut overlay = self.ruler.gpu_data(&self.camera);
+        overlay.extend(self.data.lanes.headers_gpu_data(&self.camera));
+        self.data.density_active = Density::is_active(&self.camera);
+        if self.data.density_active {
+            overlay.extend(self.data.density.gpu_data(&self.data.lanes, &self.camera));
+        }
+
+        // running sessions are drawn by the density too while it's showing, like every other log
+        let now = now() as i64;
+        if !self.data.density_active {
+            for session in self.data.sessions.running() {
+                let lane = self.data.lanes.id_for(&session.label);
+                overlay.push(session.gpu_data(lane, now));
+            }
+        }
+        overlay.extend(self.ruler.now_gpu_data(&self.camera, now as f64));
+
+        // individual logs aren't drawn under the density, so there is nothing to hover
+        self.input_state.hover = if self.input_state.cursor_inside
+            && !self.input_state.left
+            && !self.data.density_active
+        {
+            pick(&self.data, &self.camera, self.input_state.cursor_pos, now)
+        } else {
+            None
+        };
+        if let Some(selected) = &self.input_state.selected {
+            overlay.push(selection_gpu_data(selected, &self.camera));
+        }
+        if let Some(hover) = &self.input_state.hover {
+            // redraw the hovered rect over its highlight
+            overlay.push(highlight_gpu_data(hover, &self.camera));
+            overlay.push(GPU_Data::Span {
+                start: hover.start,
+                end: hover.end,
+                lane: hover.lane,
+            });
+            overlay.extend(tooltip_gpu_data(
+                hover,
+                self.input_state.cursor_pos,
+                &self.camera,
+                &self.gpu_resources.glyph_atlas,
+            ));
+        }
+
+        self.data.overlay = Some(overlay);
+        self.last_overlay_refresh = now as f64;
+        self.overlay_dirty = false;
     }
 
     fn convert_logs_to_buffs() {}
@@ -0,0 +1,259 @@
+use mosaic_model::log::Log;
+use plinth_util::time::format_timestamp;
+use winit::dpi::PhysicalPosition;
+
+use crate::{
+    camera::Camera,
+    data::Data,
+    glyph_atlas::Glyph_Atlas,
+    gpu_data::{
+        span_geometry, GPU_Data, HIGHLIGHT_COLOR, LOG_COLOR, LOG_RECT_HEIGHT, SELECTION_COLOR,
+        TOOLTIP_COLOR,
+    },
+    lanes::lane_name,
+    log_store::{log_key, Log_Key},
+    sessions::{Log_Kind, Session, Session_End},
+};
+
+// How far outside a rect the cursor can be and still hit it, thin logs would be impossible to hover otherwise
+const PICK_TOLERANCE_PX: f64 = 4.0;
+
+// Tooltip layout, in pixels
+const TOOLTIP_TEXT_SIZE: f32 = 12.0;
+const TOOLTIP_LINE_HEIGHT: f64 = 16.0;
+const TOOLTIP_PADDING: f64 = 6.0;
+const TOOLTIP_CURSOR_OFFSET: f64 = 14.0;
+
+// Whatever is under the cursor. Sessions are identified by their start log.
+#[derive(Clone, PartialEq)]
+pub struct Pick {
+    pub log: Log_Key,
+    pub start: i64,
+    pub end: i64,
+    pub lane: usize,
+    pub lines: Vec<String>, // tooltip content
+}
+
+pub fn format_duration(ms: i64) -> String {
+    let seconds = ms / 1000;
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds % 60)
+    } else {
+        format!("{}.{:03}s", seconds, ms % 1000)
+    }
+}
+
+// Finds the log or session drawn under a point on the canvas. Only the lane under the cursor is searched, and
+// instants win over sessions since they are much harder to aim at.
+pub fn pick(
+    data: &Data,
+    camera: &Camera,
+    position: PhysicalPosition<f64>,
+    now: i64,
+) -> Option<Pick> {
+    if camera.dimensions.width == 0 || camera.dimensions.height == 0 {
+        return None;
+    }
+
+    let (lane, on_header) = data.lanes.lane_at(position.y, camera)?;
+    if on_header || data.lanes.lanes[lane].collapsed {
+        return None;
+    }
+
+    let time = camera.screen_to_time(position.x);
+    let tolerance = camera.px_to_ms(PICK_TOLERANCE_PX);
+
+    let instant = data
+        .logs
+        .range(
+            (time - tolerance).floor() as i64,
+            (time + tolerance).ceil() as i64 + 1,
+        )
+        .filter(|log| data.lanes.id(lane_name(log)) == Some(lane))
+        .filter(|log| match Log_Kind::of(log) {
+            Log_Kind::Event => true,
+            // stops are only drawn on their own when they have no start
+            Log_Kind::Stop(label) => data
+                .sessions
+                .labels
+                .get(label)
+                .is_some_and(|sessions| sessions.is_orphan_stop(log.timestamp)),
+            Log_Kind::Start(_) => false,
+        })
+        .min_by(|a, b| {
+            let a = (a.timestamp as f64 - time).abs();
+            let b = (b.timestamp as f64 - time).abs();
+            a.total_cmp(&b)
+        });
+    if let Some(log) = instant {
+        return Some(instant_pick(log, lane));
+    }
+
+    // sessions with the same label never overlap, so for each label in the lane the last one to start before the
+    // cursor is the only one it can be on
+    let session = data
+        .sessions
+        .labels
+        .iter()
+        .filter(|(label, _)| data.lanes.id(label) == Some(lane))
+        .filter_map(|(_, sessions)| sessions.latest_before(time + tolerance))
+        .filter(|session| time <= session.end_or(now) as f64 + tolerance)
+        .max_by_key(|session| session.start)?;
+    let start_log = data.logs.range(session.start, session.start + 1).find(
+        |log| matches!(Log_Kind::of(log), Log_Kind::Start(label) if label == session.label),
+    )?;
+    Some(session_pick(session, start_log, lane, now))
+}
+
+// The pick for a log we already know the key of, like a selection restored from a link
+pub fn pick_log(data: &Data, key: &Log_Key, now: i64) -> Option<Pick> {
+    let log = data.logs.get(key.0, &key.1)?;
+    let lane = data.lanes.id(lane_name(log))?;
+    match Log_Kind::of(log) {
+        Log_Kind::Start(label) => {
+            let session = data
+                .sessions
+                .labels
+                .get(label)?
+                .starting_at(log.timestamp)?;
+            Some(session_pick(session, log, lane, now))
+        }
+        _ => Some(instant_pick(log, lane)),
+    }
+}
+
+fn instant_pick(log: &Log, lane: usize) -> Pick {
+    Pick {
+        log: log_key(log),
+        start: log.timestamp,
+        end: log.timestamp,
+        lane,
+        lines: vec![format_timestamp(log.timestamp), log.label.clone()],
+    }
+}
+
+fn session_pick(session: &Session, start_log: &Log, lane: usize, now: i64) -> Pick {
+    let end = match session.end {
+        Session_End::Stopped(end) => format_timestamp(end),
+        Session_End::Running => "now".to_string(),
+        Session_End::Unknown(_) => "unknown".to_string(),
+    };
+    Pick {
+        log: log_key(start_log),
+        start: session.start,
+        end: session.end_or(now),
+        lane,
+        lines: vec![
+            format!("{} - {}", format_timestamp(session.start), end),
+            format!(
+                "{} ({})",
+                session.label,
+                format_duration(session.duration(now))
+            ),
+        ],
+    }
+}
+
+// Outline behind the hovered rect, drawn a couple pixels bigger than the rect itself
+pub fn highlight_gpu_data(pick: &Pick, camera: &Camera) -> GPU_Data {
+    let border = camera.px_to_ms(2.0);
+    let (x, y, w) = span_geometry(
+        (pick.start as f64 - border) as i64,
+        (pick.end as f64 + border) as i64,
+        pick.lane,
+        camera,
+    );
+
+    GPU_Data::Rect {
+        x,
+        y,
+        w,
+        h: LOG_RECT_HEIGHT + 0.15,
+        color_index: HIGHLIGHT_COLOR,
+        fixed: 0.0,
+    }
+}
+
+// Border around the selected rect, drawn by the rect shader's outline mode
+pub fn selection_gpu_data(pick: &Pick, camera: &Camera) -> GPU_Data {
+    let border = camera.px_to_ms(3.0);
+    let (x, y, w) = span_geometry(
+        (pick.start as f64 - border) as i64,
+        (pick.end as f64 + border) as i64,
+        pick.lane,
+        camera,
+    );
+
+    GPU_Data::Outline {
+        x,
+        y,
+        w,
+        h: LOG_RECT_HEIGHT + 0.25,
+        color_index: SELECTION_COLOR,
+        fixed: 0.0,
+        thickness: 2.0,
+    }
+}
+
+// A box next to the cursor with the pick's details, kept on screen near the edges
+pub fn tooltip_gpu_data(
+    pick: &Pick,
+    cursor: PhysicalPosition<f64>,
+    camera: &Camera,
+    atlas: &Glyph_Atlas,
+) -> Vec<GPU_Data> {
+    let (width, height) = (
+        camera.dimensions.width as f64,
+        camera.dimensions.height as f64,
+    );
+    let text_width = pick
+        .lines
+        .iter()
+        .map(|line| atlas.measure(line, TOOLTIP_TEXT_SIZE) as f64)
+        .fold(0.0, f64::max);
+    let box_width = text_width + TOOLTIP_PADDING * 2.0;
+    let box_height = TOOLTIP_LINE_HEIGHT * pick.lines.len() as f64 + TOOLTIP_PADDING * 2.0;
+
+    // top left corner of the box in pixels, flipped to the other side of the cursor if it would go off screen
+    let mut left = cursor.x + TOOLTIP_CURSOR_OFFSET;
+    if left + box_width > width {
+        left = cursor.x - TOOLTIP_CURSOR_OFFSET - box_width;
+    }
+    let mut top = cursor.y + TOOLTIP_CURSOR_OFFSET;
+    if top + box_height > height {
+        top = cursor.y - TOOLTIP_CURSOR_OFFSET - box_height;
+    }
+    let to_ndc = |x: f64, y: f64| (x / width * 2.0 - 1.0, 1.0 - y / height * 2.0);
+
+    let mut data = vec![];
+    let (center_x, center_y) = to_ndc(left + box_width / 2.0, top + box_height / 2.0);
+    data.push(GPU_Data::Rect {
+        x: center_x as f32,
+        y: center_y as f32,
+        w: (box_width / width * 2.0) as f32,
+        h: (box_height / height * 2.0) as f32,
+        color_index: TOOLTIP_COLOR,
+        fixed: 1.0,
+    });
+    for (i, line) in pick.lines.iter().enumerate() {
+        // baselines sit near the bottom of each line
+        let baseline = top + TOOLTIP_PADDING + TOOLTIP_LINE_HEIGHT * (i as f64 + 1.0) - 4.0;
+        let (x, y) = to_ndc(left + TOOLTIP_PADDING, baseline);
+        data.push(GPU_Data::Text {
+            text: line.clone(),
+            x: x as f32,
+            y: y as f32,
+            size: TOOLTIP_TEXT_SIZE,
+            align: 0.0,
+            color_index: LOG_COLOR,
+            fixed: 1.0,
+        });
+    }
+
+    data
+}
@@ -4,11 +4,49 @@ use plinth_util::{logging::log, time::now};
 
 use crate::{gpu_data::GPU_Data, input_manager::Input_Manager, my_app::MyApp};
 
+// Longest frame interval animations will step by, in ms
+const MAX_FRAME_DT: f64 = 100.0;
+// Bounds on how often things that move with the current time are redrawn, in ms
+const MIN_TIME_REFRESH_MS: f64 = 16.0;
+const MAX_TIME_REFRESH_MS: f64 = 1000.0;
+// How many pixels one line of mouse wheel scrolling counts as
+const LINE_DELTA_PX: f64 = 40.0;
+
 impl PlinthApp for MyApp
 where
     MyApp: Input_Manager,
 {
     fn before_render(&mut self) {
+        // long gaps between frames (like a hidden tab) shouldn't make animations jump
+        let frame_time = now();
+        let dt = (frame_time - self.last_frame).min(MAX_FRAME_DT);
+        self.last_frame = frame_time;
+        self.coast(dt);
+        self.settle_tap();
+        self.camera.step_animation(dt);
+        self.camera.settle(dt, self.input_state.left);
+        self.camera.keep_following();
+
+        let view = self.camera.view();
+        // running sessions and the now line move with the current time, so keep them moving even when the view
+        // is still. Redrawing more often than they move a pixel is wasted.
+        let (start, end) = self.camera.visible_range();
+        let time_moves =
+            self.data.sessions.running().next().is_some() || (start..=end).contains(&frame_time);
+        let time_stale = time_moves
+            && frame_time - self.last_overlay_refresh
+                > self
+                    .camera
+                    .px_to_ms(1.0)
+                    .clamp(MIN_TIME_REFRESH_MS, MAX_TIME_REFRESH_MS);
+        if self.last_view != Some(view) || time_stale || self.overlay_dirty {
+            self.last_view = Some(view);
+            self.refresh_overlay();
+        }
+        self.sync_view_state();
+        self.sync_following();
+        self.request_ranges();
+
         // let now = now();
         // log(format!("Frame Interval: {}", now - self.frame_start).as_str());
         // self.frame_start = now;
@@ -31,20 +69,17 @@ where
                 position,
             } => self.cursor_moved(*position),
             // winit::event::WindowEvent::CursorEntered { device_id } => todo!(),
-            // winit::event::WindowEvent::CursorLeft { device_id } => todo!(),
+            winit::event::WindowEvent::CursorLeft { device_id } => self.cursor_left(),
             winit::event::WindowEvent::MouseWheel {
                 device_id,
                 delta,
                 phase,
             } => match delta {
-                winit::event::MouseScrollDelta::PixelDelta(delta) => {
-                    if delta.x.abs() > delta.y.abs() {
-                        self.h_scroll(-delta.x);
-                    } else {
-                        self.v_scroll(delta.y);
-                    }
+                winit::event::MouseScrollDelta::PixelDelta(delta) => self.wheel(delta.x, delta.y),
+                // regular mouse wheels scroll by lines
+                winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                    self.wheel(*x as f64 * LINE_DELTA_PX, *y as f64 * LINE_DELTA_PX)
                 }
-                _ => {}
             },
             winit::event::WindowEvent::MouseInput {
                 device_id,
@@ -53,13 +88,29 @@ where
             } => {
                 self.click(button, state);
             }
-            // winit::event::WindowEvent::PinchGesture { device_id, delta, phase } => todo!(),
+            winit::event::WindowEvent::PinchGesture {
+                device_id,
+                delta,
+                phase,
+            } => self.pinch(*delta),
             winit::event::WindowEvent::PanGesture {
                 device_id,
                 delta,
                 phase,
             } => self.h_scroll(delta.x as f64),
-            // winit::event::WindowEvent::Touch(touch) => todo!(),
+            winit::event::WindowEvent::Touch(touch) => self.touch(touch),
+            winit::event::WindowEvent::KeyboardInput {
+                device_id,
+                event,
+                is_synthetic,
+            } => {
+                if event.state == winit::event::ElementState::Pressed {
+                    self.key_pressed(&event.logical_key);
+                }
+            }
+            winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                self.input_state.modifiers = modifiers.state();
+            }
             winit::event::WindowEvent::Resized(dimensions) => {
                 self.camera.dimensions = *dimensions;
             }
@@ -9,7 +9,8 @@ use wgpu::{
     VertexFormat, VertexState, VertexStepMode,
 };
 
-use crate::gpu_data::GPU_Data;
+use crate::gpu_data::{GPU_Data, GRID_COLOR, TICK_COLOR, TIMELINE_COLOR};
+use crate::gpu_resources::RECT_FLOATS;
 use crate::my_app::MyApp;
 use plinth_core::graphics::Graphics;
 use plinth_core::plinth_app::PlinthRenderer;
@@ -35,14 +36,54 @@ impl PlinthRenderer for MyApp {
             b: 1.0,
             a: 1.0,
         });
-        // // create fixed rectangle, this is our timeline
-        self.data.queue.push_back(GPU_Data::Rect {
-            x: -1.0,
-            y: 0.002,
-            w: 2.0,
-            h: 0.004,
-            color_index: 0.0,
-            fixed: 1.0,
+        // define the ruler grid line color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 0.15,
+            g: 0.15,
+            b: 0.15,
+            a: 1.0,
+        });
+        // define the ruler tick color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 0.7,
+            g: 0.7,
+            b: 0.7,
+            a: 1.0,
+        });
+        // define the hover highlight color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 1.0,
+            g: 0.8,
+            b: 0.2,
+            a: 1.0,
+        });
+        // define the tooltip background color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 0.1,
+            g: 0.1,
+            b: 0.12,
+            a: 1.0,
+        });
+        // define the selection outline color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 0.3,
+            g: 0.7,
+            b: 1.0,
+            a: 1.0,
+        });
+        // define the density bar color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 0.55,
+            g: 0.75,
+            b: 0.55,
+            a: 1.0,
+        });
+        // define the now line color
+        self.data.queue.push_back(GPU_Data::Color {
+            r: 0.95,
+            g: 0.35,
+            b: 0.3,
+            a: 1.0,
         });
 
         // Initialize shader
@@ -50,17 +91,28 @@ impl PlinthRenderer for MyApp {
 
         // Initialize camera, color, and rect buffers
         self.gpu_resources.init_camera_buffer(&self.camera, gfx);
+        // Rects are positioned relative to the camera's origin
+        self.gpu_resources.rebase(self.camera.init_pos, gfx);
         // Load initial data into these buffers
         if !self.data.queue.is_empty() {
             self.gpu_resources.process_queue(&mut self.data.queue, gfx);
         }
 
-        // Initialize combined bind group that includes both color and camera data
+        // Initialize the lane layout, rects look up their vertical position in it
+        self.gpu_resources.init_lane_buffer(gfx);
+
+        // Initialize combined bind group that includes color, camera and lane data
         self.gpu_resources.init_combined_bind_group(gfx);
 
         // Initialize index buffer
         self.gpu_resources.init_index_buffer(gfx);
 
+        // Initialize the glyph atlas and the text pipeline, which shares the combined bind group with rects
+        self.gpu_resources.init_text_shader(gfx);
+        self.gpu_resources.init_atlas(gfx);
+        self.gpu_resources.init_text_pipeline_layout(gfx);
+        self.gpu_resources.init_text_pipeline(gfx);
+
         // Initialize pipeline layout and pipeline
         self.gpu_resources.init_rect_pipeline_layout(gfx);
         self.gpu_resources.init_rect_pipeline(gfx)
@@ -70,6 +122,21 @@ impl PlinthRenderer for MyApp {
         if !self.data.queue.is_empty() {
             self.gpu_resources.process_queue(&mut self.data.queue, gfx);
         }
+        // The queue was built around the old origin, so only move it once the queue is empty. The overlay has
+        // to be rebuilt around the new one before it's drawn.
+        if self.camera.needs_rebase() {
+            self.camera.rebase();
+            self.gpu_resources.rebase(self.camera.init_pos, gfx);
+            self.refresh_overlay();
+        }
+        if let Some(layout) = self.data.lane_layout.take() {
+            self.gpu_resources.write_lanes(layout, gfx);
+        }
+        if let Some(overlay) = self.data.overlay.take() {
+            self.gpu_resources.write_overlay(overlay, gfx);
+        }
+        // Upload any glyphs that text in the queue or overlay just rasterized
+        self.gpu_resources.flush_atlas(gfx);
 
         // Update camera data in the uniform buffer
         self.gpu_resources.update_camera_buffer(&self.camera, gfx);
@@ -107,20 +174,79 @@ impl PlinthRenderer for MyApp {
                 self.gpu_resources.combined_bind_group.as_ref().unwrap(),
                 &[],
             );
-            r_pass.set_vertex_buffer(
-                0,
-                self.gpu_resources.rect_buffer.as_ref().unwrap().slice(..),
-            );
             r_pass.set_index_buffer(
                 self.gpu_resources.index_buffer.as_ref().unwrap().slice(..),
                 wgpu::IndexFormat::Uint16,
             );
-            // Draw indexed instances - one for each rectangle
-            r_pass.draw_indexed(
-                0..self.gpu_resources.index_count.unwrap(),
+            // Draw indexed instances - one for each rectangle on screen, there are none until logs arrive. When
+            // zoomed out far enough the overlay has the density of the logs instead. Rects that are on screen
+            // can be spread over a few runs of the buffer, each gets its own draw.
+            if !self.data.density_active {
+                for visible in self.gpu_resources.visible_rects(&self.camera) {
+                    // the buffer is sliced rather than using first_instance, which webgl doesn't support
+                    let stride = (RECT_FLOATS * std::mem::size_of::<f32>()) as u64;
+                    r_pass.set_vertex_buffer(
+                        0,
+                        self.gpu_resources
+                            .rect_buffer
+                            .as_ref()
+                            .unwrap()
+                            .slice(visible.start as u64 * stride..visible.end as u64 * stride),
+                    );
+                    r_pass.draw_indexed(
+                        0..self.gpu_resources.index_count.unwrap(),
+                        0,
+                        0..visible.len() as u32,
+                    );
+                }
+            }
+
+            // Draw the overlay (ruler etc.) on top of the timeline with the same pipeline
+            let overlay_count = self.gpu_resources.overlay_count.unwrap_or(0);
+            if overlay_count > 0 {
+                r_pass.set_vertex_buffer(
+                    0,
+                    self.gpu_resources
+                        .overlay_buffer
+                        .as_ref()
+                        .unwrap()
+                        .slice(..),
+                );
+                r_pass.draw_indexed(
+                    0..self.gpu_resources.index_count.unwrap(),
+                    0,
+                    0..overlay_count,
+                );
+            }
+
+            // Text goes last so it is drawn over everything else
+            r_pass.set_pipeline(self.gpu_resources.text_pipeline.as_ref().unwrap());
+            r_pass.set_bind_group(
                 0,
-                0..self.gpu_resources.rect_count.unwrap(),
+                self.gpu_resources.combined_bind_group.as_ref().unwrap(),
+                &[],
+            );
+            r_pass.set_bind_group(
+                1,
+                self.gpu_resources.atlas_bind_group.as_ref().unwrap(),
+                &[],
             );
+            for (buffer, count) in [
+                (
+                    &self.gpu_resources.text_buffer,
+                    self.gpu_resources.text_count,
+                ),
+                (
+                    &self.gpu_resources.overlay_text_buffer,
+                    self.gpu_resources.overlay_text_count,
+                ),
+            ] {
+                let count = count.unwrap_or(0);
+                if count > 0 {
+                    r_pass.set_vertex_buffer(0, buffer.as_ref().unwrap().slice(..));
+                    r_pass.draw_indexed(0..self.gpu_resources.index_count.unwrap(), 0, 0..count);
+                }
+            }
         }
 
         gfx.queue.submit(Some(encoder.finish()));
@@ -0,0 +1,424 @@
+use mosaic_model::log::Log;
+
+use crate::camera::Camera;
+
+// Around the view, this many view widths on each side are kept loaded so panning doesn't run into the edge
+const PREFETCH_WIDTHS: f64 = 1.0;
+// Once something in the prefetch window is missing, this many view widths are requested at once, so small pans
+// don't each start their own request
+const REQUEST_WIDTHS: f64 = 2.0;
+const MAX_IN_FLIGHT: usize = 4;
+// After a request fails, nothing new is requested for a while, longer each failure in a row
+const MIN_RETRY_MS: f64 = 1000.0;
+const MAX_RETRY_MS: f64 = 30000.0;
+// Loaded time is never quite up to now, this close counts as reaching it. Without it, the edge of the loaded
+// time falling behind now would be a new gap to fetch every frame.
+const EDGE_TOLERANCE_MS: f64 = 60000.0;
+
+// Sorted, non overlapping time ranges. Touching ranges are merged.
+pub struct Range_Set {
+    pub ranges: Vec<(f64, f64)>,
+}
+
+impl Range_Set {
+    pub fn new() -> Self {
+        Self { ranges: vec![] }
+    }
+
+    pub fn insert(&mut self, start: f64, end: f64) {
+        if end <= start {
+            return;
+        }
+        // everything overlapping or touching the new range is folded into it
+        let first = self.ranges.partition_point(|(_, e)| *e < start);
+        let last = self.ranges.partition_point(|(s, _)| *s <= end);
+        let mut merged = (start, end);
+        if first < last {
+            merged.0 = merged.0.min(self.ranges[first].0);
+            merged.1 = merged.1.max(self.ranges[last - 1].1);
+        }
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, start: f64, end: f64) {
+        if end <= start {
+            return;
+        }
+        let mut kept = vec![];
+        for &(s, e) in &self.ranges {
+            if e <= start || s >= end {
+                kept.push((s, e));
+                continue;
+            }
+            // whatever sticks out either side of the removed range stays
+            if s < start {
+                kept.push((s, start));
+            }
+            if e > end {
+                kept.push((end, e));
+            }
+        }
+        self.ranges = kept;
+    }
+
+    // The parts of start to end not covered by any range
+    pub fn gaps(&self, start: f64, end: f64) -> Vec<(f64, f64)> {
+        let mut gaps = vec![];
+        let mut cursor = start;
+        for &(s, e) in &self.ranges {
+            if e <= cursor {
+                continue;
+            }
+            if s >= end {
+                break;
+            }
+            if s > cursor {
+                gaps.push((cursor, s));
+            }
+            cursor = e;
+        }
+        if cursor < end {
+            gaps.push((cursor, end));
+        }
+        gaps
+    }
+}
+
+// Cuts cached logs down to size: logs older than oldest go, then the oldest of the rest until their lines fit in
+// max_bytes, along with the ranges they were in. lines holds the line each log came from, in the same order.
+// Returns the logs and body that are left, and whether anything was dropped.
+pub fn trim_cached(
+    logs: Vec<Log>,
+    lines: &str,
+    ranges: &mut Range_Set,
+    oldest: f64,
+    max_bytes: usize,
+) -> (Vec<Log>, String, bool) {
+    let mut entries: Vec<(Log, &str)> = logs.into_iter().zip(lines.lines()).collect();
+    entries.sort_by_key(|(log, _)| std::cmp::Reverse(log.timestamp));
+
+    // everything before cutoff goes. Logs with the same timestamp stay or go together.
+    let mut cutoff = oldest;
+    let mut bytes = 0;
+    for (log, line) in &entries {
+        bytes += line.len() + 1;
+        if bytes > max_bytes {
+            cutoff = cutoff.max(log.timestamp as f64 + 1.0);
+            break;
+        }
+    }
+
+    let count = entries.len();
+    entries.retain(|(log, _)| log.timestamp as f64 >= cutoff);
+    let cached_before = ranges
+        .ranges
+        .first()
+        .is_some_and(|(start, _)| *start < cutoff);
+    ranges.remove(f64::NEG_INFINITY, cutoff);
+    let trimmed = entries.len() < count || cached_before;
+
+    entries.reverse();
+    let body = entries
+        .iter()
+        .map(|(_, line)| *line)
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let logs = entries.into_iter().map(|(log, _)| log).collect();
+    (logs, body, trimmed)
+}
+
+// Keeps track of which time ranges have been fetched and decides what to fetch next as the view moves
+pub struct Range_Loader {
+    pub loaded: Range_Set,
+    pub requested: Range_Set, // asked for but not arrived yet
+    in_flight: usize,
+    failures: u32,           // failed requests in a row
+    retry_after: f64,        // no new requests before this time, in ms
+    live_since: Option<f64>, // when the stream connected, None while it's down
+}
+
+impl Range_Loader {
+    pub fn new() -> Self {
+        Self {
+            loaded: Range_Set::new(),
+            requested: Range_Set::new(),
+            in_flight: 0,
+            failures: 0,
+            retry_after: 0.0,
+            live_since: None,
+        }
+    }
+
+    // Ranges that should be requested now for the camera's view, already marked as requested. There's nothing
+    // to load past now, logs newer than that come in over the stream.
+    pub fn update(&mut self, camera: &Camera, now: f64) -> Vec<(f64, f64)> {
+        self.extend_live(now);
+        if self.in_flight >= MAX_IN_FLIGHT || now < self.retry_after {
+            return vec![];
+        }
+        let (start, end) = camera.visible_range();
+        let width = end - start;
+        let wanted_end = (end + width * PREFETCH_WIDTHS).min(now - EDGE_TOLERANCE_MS);
+        if self
+            .missing(start - width * PREFETCH_WIDTHS, wanted_end)
+            .is_empty()
+        {
+            return vec![];
+        }
+
+        let mut requests = self.missing(
+            start - width * REQUEST_WIDTHS,
+            (end + width * REQUEST_WIDTHS).min(now),
+        );
+        requests.truncate(MAX_IN_FLIGHT - self.in_flight);
+        for &(start, end) in &requests {
+            self.requested.insert(start, end);
+            self.in_flight += 1;
+        }
+        requests
+    }
+
+    // While the stream is connected every new log arrives over it, so loaded time that reaches the moment it
+    // connected keeps reaching now
+    fn extend_live(&mut self, now: f64) {
+        let Some(live_since) = self.live_since else {
+            return;
+        };
+        if let Some(last) = self.loaded.ranges.last_mut() {
+            if last.1 >= live_since {
+                last.1 = last.1.max(now);
+            }
+        }
+    }
+
+    pub fn set_stream_connected(&mut self, connected: bool, now: f64) {
+        self.live_since = connected.then_some(now);
+    }
+
+    // Where the view can go: everything loaded, and far enough past its start for the prefetch to reach older
+    // logs. When all time before some point is loaded there's nothing older to reach, so the first log is the
+    // start instead. None until something has loaded.
+    pub fn bounds(&self, camera: &Camera, now: f64, first_log: Option<f64>) -> Option<(f64, f64)> {
+        let (first, last) = (self.loaded.ranges.first()?, self.loaded.ranges.last()?);
+        let (start, end) = camera.visible_range();
+        let margin = (end - start) * PREFETCH_WIDTHS;
+        let first = if first.0.is_finite() {
+            first.0 - margin
+        } else {
+            first_log.unwrap_or(now)
+        };
+        Some((first.min(now), last.1.max(now)))
+    }
+
+    fn missing(&self, start: f64, end: f64) -> Vec<(f64, f64)> {
+        if end <= start {
+            return vec![];
+        }
+        let mut missing = vec![];
+        for (start, end) in self.loaded.gaps(start, end) {
+            missing.extend(self.requested.gaps(start, end));
+        }
+        missing
+    }
+
+    // Where the loaded time after a timestamp first runs out, None if it reaches (close enough to) now. Logs past
+    // that point may be missing. A timestamp that isn't loaded at all runs out right away.
+    pub fn gap_after(&self, timestamp: f64, now: f64) -> Option<f64> {
+        let gap = match self
+            .loaded
+            .ranges
+            .iter()
+            .find(|(start, end)| *start <= timestamp && timestamp <= *end)
+        {
+            Some(&(_, end)) => end,
+            None => timestamp,
+        };
+        (gap < now - EDGE_TOLERANCE_MS).then_some(gap)
+    }
+
+    pub fn loaded(&mut self, start: f64, end: f64) {
+        self.requested.remove(start, end);
+        self.loaded.insert(start, end);
+        self.in_flight = self.in_flight.saturating_sub(1);
+        self.failures = 0;
+    }
+
+    // For ranges that were never requested, like ones restored from the cache
+    pub fn mark_loaded(&mut self, start: f64, end: f64) {
+        self.loaded.insert(start, end);
+    }
+
+    // The range goes back to missing, to be asked for again once the retry delay is up
+    pub fn failed(&mut self, start: f64, end: f64, now: f64) {
+        self.requested.remove(start, end);
+        self.in_flight = self.in_flight.saturating_sub(1);
+        let delay = MIN_RETRY_MS * 2f64.powi(self.failures as i32);
+        self.retry_after = now + delay.min(MAX_RETRY_MS);
+        self.failures += 1;
+    }
+
+    // Forgets everything loaded after a timestamp, so it's fetched again. Used when logs may have been missed,
+    // like while the stream was disconnected.
+    pub fn forget_after(&mut self, timestamp: f64) {
+        self.loaded.remove(timestamp, f64::INFINITY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ranges: &[(f64, f64)]) -> Range_Set {
+        let mut set = Range_Set::new();
+        for &(start, end) in ranges {
+            set.insert(start, end);
+        }
+        set
+    }
+
+    #[test]
+    fn insert_keeps_ranges_sorted() {
+        let set = set(&[(50.0, 60.0), (10.0, 20.0), (30.0, 40.0)]);
+        assert_eq!(set.ranges, vec![(10.0, 20.0), (30.0, 40.0), (50.0, 60.0)]);
+    }
+
+    #[test]
+    fn insert_merges_touching_and_overlapping_ranges() {
+        assert_eq!(
+            set(&[(10.0, 20.0), (20.0, 30.0)]).ranges,
+            vec![(10.0, 30.0)]
+        );
+        assert_eq!(
+            set(&[(10.0, 20.0), (15.0, 30.0)]).ranges,
+            vec![(10.0, 30.0)]
+        );
+        assert_eq!(
+            set(&[(10.0, 40.0), (15.0, 30.0)]).ranges,
+            vec![(10.0, 40.0)]
+        );
+        // one range can swallow several
+        assert_eq!(
+            set(&[(10.0, 20.0), (30.0, 40.0), (50.0, 60.0), (15.0, 55.0)]).ranges,
+            vec![(10.0, 60.0)]
+        );
+    }
+
+    #[test]
+    fn insert_ignores_empty_ranges() {
+        assert!(set(&[(10.0, 10.0), (20.0, 5.0)]).ranges.is_empty());
+    }
+
+    #[test]
+    fn remove_splits_ranges() {
+        let mut set = set(&[(10.0, 50.0)]);
+        set.remove(20.0, 30.0);
+        assert_eq!(set.ranges, vec![(10.0, 20.0), (30.0, 50.0)]);
+
+        // trims the ends of ranges it overlaps, and drops the ones it covers
+        let mut set = self::set(&[(10.0, 20.0), (30.0, 40.0), (50.0, 60.0)]);
+        set.remove(15.0, 55.0);
+        assert_eq!(set.ranges, vec![(10.0, 15.0), (55.0, 60.0)]);
+
+        set.remove(0.0, f64::INFINITY);
+        assert!(set.ranges.is_empty());
+    }
+
+    #[test]
+    fn gaps_are_the_uncovered_parts() {
+        let set = set(&[(10.0, 20.0), (30.0, 40.0)]);
+        assert_eq!(
+            set.gaps(0.0, 50.0),
+            vec![(0.0, 10.0), (20.0, 30.0), (40.0, 50.0)]
+        );
+        assert_eq!(set.gaps(15.0, 35.0), vec![(20.0, 30.0)]);
+        assert!(set.gaps(12.0, 18.0).is_empty());
+        assert_eq!(Range_Set::new().gaps(0.0, 5.0), vec![(0.0, 5.0)]);
+    }
+
+    fn cached(entries: &[(i64, &str)]) -> (Vec<Log>, String) {
+        let logs = entries
+            .iter()
+            .map(|(timestamp, label)| Log {
+                label: label.to_string(),
+                timestamp: *timestamp,
+            })
+            .collect();
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|(timestamp, label)| format!("{},{}", label, timestamp))
+            .collect();
+        (logs, lines.join("\n"))
+    }
+
+    fn timestamps(logs: &[Log]) -> Vec<i64> {
+        logs.iter().map(|log| log.timestamp).collect()
+    }
+
+    #[test]
+    fn trim_keeps_everything_that_fits() {
+        let (logs, lines) = cached(&[(100, "a"), (200, "b")]);
+        let mut ranges = set(&[(50.0, 250.0)]);
+        let (logs, body, trimmed) = trim_cached(logs, &lines, &mut ranges, 0.0, 1000);
+        assert!(!trimmed);
+        assert_eq!(timestamps(&logs), vec![100, 200]);
+        assert_eq!(body, lines);
+        assert_eq!(ranges.ranges, vec![(50.0, 250.0)]);
+    }
+
+    #[test]
+    fn trim_drops_logs_and_ranges_that_are_too_old() {
+        let (logs, lines) = cached(&[(100, "a"), (200, "b"), (300, "c")]);
+        let mut ranges = set(&[(50.0, 120.0), (150.0, 350.0)]);
+        let (logs, body, trimmed) = trim_cached(logs, &lines, &mut ranges, 180.0, 1000);
+        assert!(trimmed);
+        assert_eq!(timestamps(&logs), vec![200, 300]);
+        assert_eq!(body, "b,200\nc,300");
+        assert_eq!(ranges.ranges, vec![(180.0, 350.0)]);
+    }
+
+    #[test]
+    fn trim_drops_the_oldest_logs_past_the_size_limit() {
+        // every line is 6 bytes with its newline
+        let (logs, lines) = cached(&[(100, "a"), (200, "b"), (300, "c"), (400, "d")]);
+        let mut ranges = set(&[(0.0, 500.0)]);
+        let (logs, body, trimmed) = trim_cached(logs, &lines, &mut ranges, 0.0, 12);
+        assert!(trimmed);
+        assert_eq!(timestamps(&logs), vec![300, 400]);
+        assert_eq!(body, "c,300\nd,400");
+        // the cut off time isn't complete anymore
+        assert_eq!(ranges.ranges, vec![(201.0, 500.0)]);
+    }
+
+    #[test]
+    fn trim_keeps_or_drops_a_timestamp_as_a_whole() {
+        let (logs, lines) = cached(&[(100, "a"), (200, "b"), (200, "c"), (300, "d")]);
+        let mut ranges = set(&[(0.0, 500.0)]);
+        // room for two lines would split the logs at 200, so both of them go
+        let (logs, _, trimmed) = trim_cached(logs, &lines, &mut ranges, 0.0, 12);
+        assert!(trimmed);
+        assert_eq!(timestamps(&logs), vec![300]);
+        assert_eq!(ranges.ranges, vec![(201.0, 500.0)]);
+    }
+
+    #[test]
+    fn trim_reports_ranges_dropped_without_logs() {
+        let mut ranges = set(&[(10.0, 20.0), (100.0, 200.0)]);
+        let (logs, body, trimmed) = trim_cached(vec![], "", &mut ranges, 50.0, 1000);
+        assert!(trimmed);
+        assert!(logs.is_empty());
+        assert_eq!(body, "");
+        assert_eq!(ranges.ranges, vec![(100.0, 200.0)]);
+    }
+
+    #[test]
+    fn gap_after_follows_the_loaded_range() {
+        let mut loader = Range_Loader::new();
+        loader.mark_loaded(1000.0, 2000.0);
+        let now = 1_000_000.0;
+        assert_eq!(loader.gap_after(1500.0, now), Some(2000.0));
+        assert_eq!(loader.gap_after(3000.0, now), Some(3000.0));
+
+        loader.mark_loaded(2000.0, now - 10.0);
+        assert_eq!(loader.gap_after(1500.0, now), None);
+    }
+}
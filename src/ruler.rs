@@ -0,0 +1,472 @@
+use plinth_util::time::format_timestamp;
+
+use crate::{
+    camera::Camera,
+    gpu_data::{GPU_Data, GRID_COLOR, NOW_COLOR, TICK_COLOR},
+};
+
+static ms_per_second: i64 = 1000;
+static ms_per_minute: i64 = ms_per_second * 60;
+static ms_per_hour: i64 = ms_per_minute * 60;
+static ms_per_day: i64 = ms_per_hour * 24;
+
+// Ticks closer together than this (in pixels) are too dense to be useful.
+const MIN_MAJOR_SPACING: f64 = 160.0; // leaves room for a label
+const MIN_MINOR_SPACING: f64 = 12.0;
+
+// Ruler layout, in pixels from the bottom of the canvas
+const BASELINE_OFFSET: f64 = 24.0;
+const MAJOR_TICK_HEIGHT: f64 = 12.0;
+const MINOR_TICK_HEIGHT: f64 = 6.0;
+const LABEL_SIZE: f32 = 12.0;
+const LABEL_GAP: f64 = 4.0; // space between the tick ends and the label
+const NOW_LINE_WIDTH: f64 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interval {
+    Millis(i64),
+    Weeks,
+    Months(i64),
+    Years(i64),
+}
+
+// Each major interval paired with the interval used to subdivide it, ordered from finest to coarsest.
+static INTERVALS: [(Interval, Interval); 23] = [
+    (Interval::Millis(ms_per_second), Interval::Millis(100)),
+    (
+        Interval::Millis(5 * ms_per_second),
+        Interval::Millis(ms_per_second),
+    ),
+    (
+        Interval::Millis(15 * ms_per_second),
+        Interval::Millis(5 * ms_per_second),
+    ),
+    (
+        Interval::Millis(30 * ms_per_second),
+        Interval::Millis(5 * ms_per_second),
+    ),
+    (
+        Interval::Millis(ms_per_minute),
+        Interval::Millis(15 * ms_per_second),
+    ),
+    (
+        Interval::Millis(5 * ms_per_minute),
+        Interval::Millis(ms_per_minute),
+    ),
+    (
+        Interval::Millis(15 * ms_per_minute),
+        Interval::Millis(5 * ms_per_minute),
+    ),
+    (
+        Interval::Millis(30 * ms_per_minute),
+        Interval::Millis(5 * ms_per_minute),
+    ),
+    (
+        Interval::Millis(ms_per_hour),
+        Interval::Millis(15 * ms_per_minute),
+    ),
+    (
+        Interval::Millis(3 * ms_per_hour),
+        Interval::Millis(ms_per_hour),
+    ),
+    (
+        Interval::Millis(6 * ms_per_hour),
+        Interval::Millis(ms_per_hour),
+    ),
+    (
+        Interval::Millis(12 * ms_per_hour),
+        Interval::Millis(3 * ms_per_hour),
+    ),
+    (
+        Interval::Millis(ms_per_day),
+        Interval::Millis(3 * ms_per_hour),
+    ),
+    (Interval::Weeks, Interval::Millis(ms_per_day)),
+    (Interval::Months(1), Interval::Millis(ms_per_day)),
+    (Interval::Months(3), Interval::Months(1)),
+    (Interval::Months(6), Interval::Months(1)),
+    (Interval::Years(1), Interval::Months(1)),
+    (Interval::Years(5), Interval::Years(1)),
+    (Interval::Years(10), Interval::Years(1)),
+    (Interval::Years(50), Interval::Years(10)),
+    (Interval::Years(100), Interval::Years(10)),
+    (Interval::Years(500), Interval::Years(100)),
+];
+
+impl Interval {
+    // Rough length, only used to decide which interval fits the current zoom level
+    pub fn approx_ms(&self) -> f64 {
+        match self {
+            Interval::Millis(ms) => *ms as f64,
+            Interval::Weeks => (7 * ms_per_day) as f64,
+            Interval::Months(n) => *n as f64 * 30.44 * ms_per_day as f64,
+            Interval::Years(n) => *n as f64 * 365.25 * ms_per_day as f64,
+        }
+    }
+
+    // Latest tick at or before the timestamp
+    pub fn floor(&self, timestamp: i64) -> i64 {
+        match self {
+            Interval::Millis(ms) => timestamp.div_euclid(*ms) * ms,
+            Interval::Weeks => {
+                // 1970-01-05 was the first Monday after the epoch
+                let monday = 4 * ms_per_day;
+                let week = 7 * ms_per_day;
+                (timestamp - monday).div_euclid(week) * week + monday
+            }
+            Interval::Months(n) => {
+                let (year, month, _) = civil_from_days(timestamp.div_euclid(ms_per_day));
+                let months = year * 12 + (month - 1);
+                let months = months.div_euclid(*n) * n;
+                days_from_civil(months.div_euclid(12), months.rem_euclid(12) + 1, 1) * ms_per_day
+            }
+            Interval::Years(n) => {
+                let (year, _, _) = civil_from_days(timestamp.div_euclid(ms_per_day));
+                days_from_civil(year.div_euclid(*n) * n, 1, 1) * ms_per_day
+            }
+        }
+    }
+
+    // The tick following one that is already aligned to this interval
+    pub fn next(&self, timestamp: i64) -> i64 {
+        match self {
+            Interval::Millis(ms) => timestamp + ms,
+            Interval::Weeks => timestamp + 7 * ms_per_day,
+            Interval::Months(n) => {
+                let (year, month, _) = civil_from_days(timestamp.div_euclid(ms_per_day));
+                let months = year * 12 + (month - 1) + n;
+                days_from_civil(months.div_euclid(12), months.rem_euclid(12) + 1, 1) * ms_per_day
+            }
+            Interval::Years(n) => {
+                let (year, _, _) = civil_from_days(timestamp.div_euclid(ms_per_day));
+                days_from_civil(year + n, 1, 1) * ms_per_day
+            }
+        }
+    }
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date, see http://howardhinnant.github.io/date_algorithms.html
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12; // March is 0
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Inverse of days_from_civil, returns (year, month, day)
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Offset of the browser's timezone from UTC at a timestamp, so ticks land on local midnights and month starts.
+// Asked for each timestamp, the offset changes across daylight saving time.
+fn local_offset_ms(timestamp: i64) -> i64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        // getTimezoneOffset is in minutes and positive west of UTC
+        let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(timestamp as f64));
+        return -(date.get_timezone_offset() as i64) * ms_per_minute;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    0
+}
+
+// Local wall clock time, as ms since the epoch as if it were UTC
+fn utc_to_local(timestamp: i64) -> i64 {
+    timestamp + local_offset_ms(timestamp)
+}
+
+// The offset has to be looked up at the utc time, which isn't known yet, so it's found from a first guess
+fn local_to_utc(local: i64) -> i64 {
+    let guess = local - local_offset_ms(local);
+    local - local_offset_ms(guess)
+}
+
+static MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Label for a major tick, only as precise as the interval between ticks needs. format_timestamp gives
+// "MM-DD-YYYY: hh:mm a" in local time, its date or its time is used depending on the interval. It has no seconds,
+// months or years on their own, those are added here.
+fn label_text(major: Interval, timestamp: i64) -> String {
+    let formatted = format_timestamp(timestamp);
+    let (date, time) = formatted
+        .split_once(": ")
+        .unwrap_or((formatted.as_str(), ""));
+    let local = utc_to_local(timestamp);
+    let time_of_day = local.rem_euclid(ms_per_day);
+    match major {
+        // a new day is worth pointing out even between times
+        Interval::Millis(_) if time_of_day == 0 => date.to_string(),
+        Interval::Millis(ms) if ms < ms_per_minute => {
+            // "hh:mm a" becomes "hh:mm:ss a"
+            let second = time_of_day / ms_per_second % 60;
+            match time.split_once(' ') {
+                Some((clock, period)) => format!("{}:{:02} {}", clock, second, period),
+                None => format!("{}:{:02}", time, second),
+            }
+        }
+        Interval::Millis(ms) if ms < ms_per_day => time.to_string(),
+        Interval::Millis(_) | Interval::Weeks => date.to_string(),
+        Interval::Months(_) => {
+            let (year, month, _) = civil_from_days(local.div_euclid(ms_per_day));
+            format!("{} {}", MONTH_NAMES[(month - 1) as usize], year)
+        }
+        Interval::Years(_) => civil_from_days(local.div_euclid(ms_per_day)).0.to_string(),
+    }
+}
+
+pub struct Tick {
+    pub timestamp: i64,
+    pub major: bool,
+}
+
+pub struct Label {
+    pub x: f32, // horizontal center of the label in NDC
+    pub text: String,
+}
+
+pub struct Ruler {
+    pub ticks: Vec<Tick>,
+    pub labels: Vec<Label>,
+    pub major: Option<Interval>,
+    pub minor: Option<Interval>,
+}
+
+impl Ruler {
+    pub fn new() -> Self {
+        Self {
+            ticks: vec![],
+            labels: vec![],
+            major: None,
+            minor: None,
+        }
+    }
+
+    // Pick the finest major interval that leaves room for a label, and its subdivision if it isn't too dense.
+    pub fn choose_intervals(camera: &Camera) -> (Option<Interval>, Option<Interval>) {
+        for (major, minor) in INTERVALS.iter() {
+            if camera.ms_to_px(major.approx_ms()) >= MIN_MAJOR_SPACING {
+                let minor = if camera.ms_to_px(minor.approx_ms()) >= MIN_MINOR_SPACING {
+                    Some(*minor)
+                } else {
+                    None
+                };
+                return (Some(*major), minor);
+            }
+        }
+        (None, None)
+    }
+
+    // Recompute the ticks and labels for the camera's current view.
+    pub fn update(&mut self, camera: &Camera) {
+        self.ticks.clear();
+        self.labels.clear();
+        if camera.dimensions.width == 0 || camera.dimensions.height == 0 {
+            return;
+        }
+
+        let (major, minor) = Self::choose_intervals(camera);
+        self.major = major;
+        self.minor = minor;
+
+        // ticks are stepped through in local time, then each is converted back on its own
+        let (start, end) = camera.visible_range();
+        let (start, end) = (utc_to_local(start as i64), utc_to_local(end as i64));
+
+        if let Some(major) = major {
+            let mut t = major.floor(start);
+            while t <= end {
+                let timestamp = local_to_utc(t);
+                self.ticks.push(Tick {
+                    timestamp,
+                    major: true,
+                });
+                self.labels.push(Label {
+                    x: camera.time_to_ndc(timestamp as f64) as f32,
+                    text: label_text(major, timestamp),
+                });
+                t = major.next(t);
+            }
+        }
+
+        if let (Some(major), Some(minor)) = (major, minor) {
+            let mut t = minor.floor(start);
+            while t <= end {
+                // major ticks already cover this spot
+                if major.floor(t) != t {
+                    self.ticks.push(Tick {
+                        timestamp: local_to_utc(t),
+                        major: false,
+                    });
+                }
+                t = minor.next(t);
+            }
+        }
+    }
+
+    // Tick marks and grid lines as screen fixed rects, with the labels under the major ticks.
+    pub fn gpu_data(&self, camera: &Camera) -> Vec<GPU_Data> {
+        let mut data = vec![];
+        if camera.dimensions.width == 0 || camera.dimensions.height == 0 {
+            return data;
+        }
+
+        let px_w = 2.0 / camera.dimensions.width as f64;
+        let px_h = 2.0 / camera.dimensions.height as f64;
+        let baseline = -1.0 + BASELINE_OFFSET * px_h;
+
+        // the baseline the ticks hang off of
+        data.push(GPU_Data::Rect {
+            x: 0.0,
+            y: baseline as f32,
+            w: 2.0,
+            h: px_h as f32,
+            color_index: TICK_COLOR,
+            fixed: 1.0,
+        });
+
+        for tick in &self.ticks {
+            let x = camera.time_to_ndc(tick.timestamp as f64) as f32;
+            let height = if tick.major {
+                MAJOR_TICK_HEIGHT
+            } else {
+                MINOR_TICK_HEIGHT
+            } * px_h;
+
+            if tick.major {
+                // faint grid line running up the whole view so logs can be lined up with the ruler
+                data.push(GPU_Data::Rect {
+                    x,
+                    y: ((1.0 + baseline) / 2.0) as f32,
+                    w: px_w as f32,
+                    h: (1.0 - baseline) as f32,
+                    color_index: GRID_COLOR,
+                    fixed: 1.0,
+                });
+            }
+            data.push(GPU_Data::Rect {
+                x,
+                y: (baseline - height / 2.0) as f32,
+                w: px_w as f32,
+                h: height as f32,
+                color_index: TICK_COLOR,
+                fixed: 1.0,
+            });
+        }
+
+        // label baselines sit below the major ticks, clear of the text's ascent
+        let label_y = baseline - (MAJOR_TICK_HEIGHT + LABEL_GAP) * px_h - LABEL_SIZE as f64 * px_h;
+        for label in &self.labels {
+            data.push(GPU_Data::Text {
+                text: label.text.clone(),
+                x: label.x,
+                y: label_y as f32,
+                size: LABEL_SIZE,
+                align: 0.5,
+                color_index: TICK_COLOR,
+                fixed: 1.0,
+            });
+        }
+
+        data
+    }
+
+    // A line marking the current time, running from the ruler's ticks up through the lanes. Empty when now is
+    // off screen.
+    pub fn now_gpu_data(&self, camera: &Camera, now: f64) -> Vec<GPU_Data> {
+        let x = camera.time_to_ndc(now);
+        if camera.dimensions.width == 0 || camera.dimensions.height == 0 || x.abs() > 1.0 {
+            return vec![];
+        }
+        let px_w = 2.0 / camera.dimensions.width as f64;
+        let px_h = 2.0 / camera.dimensions.height as f64;
+        let bottom = -1.0 + (BASELINE_OFFSET - MAJOR_TICK_HEIGHT) * px_h;
+        vec![GPU_Data::Rect {
+            x: x as f32,
+            y: ((1.0 + bottom) / 2.0) as f32,
+            w: (NOW_LINE_WIDTH * px_w) as f32,
+            h: (1.0 - bottom) as f32,
+            color_index: NOW_COLOR,
+            fixed: 1.0,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-03-14 at a time of day, local time is UTC in native builds
+    fn at(hour: i64, minute: i64, second: i64) -> i64 {
+        local_to_utc(
+            days_from_civil(2024, 3, 14) * ms_per_day
+                + hour * ms_per_hour
+                + minute * ms_per_minute
+                + second * ms_per_second,
+        )
+    }
+
+    // format_timestamp's date and time, whatever timezone the tests run in
+    fn date_and_time(timestamp: i64) -> (String, String) {
+        let formatted = format_timestamp(timestamp);
+        let (date, time) = formatted.split_once(": ").unwrap();
+        (date.to_string(), time.to_string())
+    }
+
+    #[test]
+    fn labels_match_the_interval() {
+        let t = at(15, 4, 5);
+        let (date, time) = date_and_time(t);
+        let seconds = label_text(Interval::Millis(5 * ms_per_second), t);
+        assert_eq!(seconds.replace(":05", ""), time);
+        assert_ne!(seconds, time);
+        assert_eq!(label_text(Interval::Millis(ms_per_hour), t), time);
+        assert_eq!(label_text(Interval::Millis(ms_per_day), t), date);
+        assert_eq!(label_text(Interval::Weeks, t), date);
+        assert_eq!(label_text(Interval::Months(1), t), "Mar 2024");
+        assert_eq!(label_text(Interval::Years(10), t), "2024");
+    }
+
+    #[test]
+    fn consecutive_second_ticks_get_different_labels() {
+        let interval = Interval::Millis(ms_per_second);
+        let t = at(9, 30, 0);
+        assert_ne!(
+            label_text(interval, t),
+            label_text(interval, t + ms_per_second)
+        );
+    }
+
+    #[test]
+    fn midnight_reads_as_the_date() {
+        let midnight = at(0, 0, 0);
+        assert_eq!(
+            label_text(Interval::Millis(ms_per_hour), midnight),
+            date_and_time(midnight).0
+        );
+        let after = at(0, 1, 0);
+        assert_eq!(
+            label_text(Interval::Millis(ms_per_minute), after),
+            date_and_time(after).1
+        );
+    }
+}
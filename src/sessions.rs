@@ -0,0 +1,500 @@
+use std::collections::{HashMap, HashSet};
+
+use mosaic_model::log::Log;
+
+use crate::gpu_data::GPU_Data;
+
+// What a log means for session pairing, based on its label
+pub enum Log_Kind<'a> {
+    Start(&'a str),
+    Stop(&'a str),
+    Event,
+}
+
+impl<'a> Log_Kind<'a> {
+    pub fn of(log: &'a Log) -> Self {
+        if let Some(label) = log.label.strip_prefix("start ") {
+            Log_Kind::Start(label.trim())
+        } else if let Some(label) = log.label.strip_prefix("stop ") {
+            Log_Kind::Stop(label.trim())
+        } else {
+            Log_Kind::Event
+        }
+    }
+}
+
+// How a session ended, as far as the loaded logs can tell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Session_End {
+    Stopped(i64),
+    Running, // no stop, and everything from the start up to now is loaded
+    // no stop in the loaded logs, but they only reach this far, its stop may be in a range that isn't loaded yet
+    Unknown(i64),
+}
+
+// A start log and the stop log that closed it, or just the start if it is still going
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub label: String,
+    pub start: i64,
+    pub end: Session_End,
+}
+
+impl Session {
+    // Running sessions extend to the current time, unknown ones as far as is known
+    pub fn end_or(&self, now: i64) -> i64 {
+        let end = match self.end {
+            Session_End::Stopped(end) | Session_End::Unknown(end) => end,
+            Session_End::Running => now,
+        };
+        end.max(self.start)
+    }
+
+    pub fn duration(&self, now: i64) -> i64 {
+        self.end_or(now) - self.start
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.end == Session_End::Running
+    }
+
+    pub fn gpu_data(&self, lane: usize, now: i64) -> GPU_Data {
+        GPU_Data::Span {
+            start: self.start,
+            end: self.end_or(now),
+            lane,
+        }
+    }
+}
+
+// A stop log with no start before it, kept so it can still be drawn as an instant
+#[derive(Clone, Debug, PartialEq)]
+pub struct Orphan_Stop {
+    pub label: String,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session_Stats {
+    pub label: String,
+    pub count: usize,
+    pub total_ms: i64,
+    pub running: bool,
+}
+
+// A span that went into or has to come out of the rect buffer, as (start, end, label)
+pub type Span = (i64, i64, String);
+
+// Everything paired for one label. Only one session per label can be open at a time, so at most the last one
+// has no stop.
+pub struct Label_Sessions {
+    pub sessions: Vec<Session>,         // ordered by start time
+    pub orphan_stops: Vec<Orphan_Stop>, // ordered by time
+    marks: Vec<(i64, bool)>, // every start and stop log as (timestamp, is stop), ordered by time
+}
+
+impl Label_Sessions {
+    fn new() -> Self {
+        Self {
+            sessions: vec![],
+            orphan_stops: vec![],
+            marks: vec![],
+        }
+    }
+
+    // Pairs every stop with the start before it. A repeated start while a session is running is treated as part
+    // of the session that's already going.
+    fn pair(&mut self, label: &str) {
+        self.sessions.clear();
+        self.orphan_stops.clear();
+        let mut open = false;
+        for &(timestamp, is_stop) in &self.marks {
+            match (is_stop, open) {
+                (false, false) => {
+                    open = true;
+                    self.sessions.push(Session {
+                        label: label.to_string(),
+                        start: timestamp,
+                        end: Session_End::Running,
+                    });
+                }
+                (false, true) => {}
+                (true, true) => {
+                    open = false;
+                    self.sessions.last_mut().unwrap().end = Session_End::Stopped(timestamp);
+                }
+                (true, false) => self.orphan_stops.push(Orphan_Stop {
+                    label: label.to_string(),
+                    timestamp,
+                }),
+            }
+        }
+    }
+
+    // The one session that can have no stop
+    fn open(&mut self) -> Option<&mut Session> {
+        self.sessions
+            .last_mut()
+            .filter(|session| !matches!(session.end, Session_End::Stopped(_)))
+    }
+
+    // Spans that live in the rect buffer. Running sessions grow every frame, so they're drawn in the overlay
+    // instead.
+    fn fixed_spans(&self) -> impl Iterator<Item = Span> + '_ {
+        let stops = self
+            .orphan_stops
+            .iter()
+            .map(|stop| (stop.timestamp, stop.timestamp, stop.label.clone()));
+        self.sessions.iter().filter_map(fixed_span).chain(stops)
+    }
+
+    // The spans an update can change: all of them when the label is paired again, otherwise just the open one's
+    fn changing_spans(&self, repair: bool) -> Vec<Span> {
+        if repair {
+            return self.fixed_spans().collect();
+        }
+        self.sessions
+            .last()
+            .filter(|session| !matches!(session.end, Session_End::Stopped(_)))
+            .and_then(fixed_span)
+            .into_iter()
+            .collect()
+    }
+
+    // The session that starts at a timestamp
+    pub fn starting_at(&self, timestamp: i64) -> Option<&Session> {
+        let index = self
+            .sessions
+            .partition_point(|session| session.start < timestamp);
+        self.sessions
+            .get(index)
+            .filter(|session| session.start == timestamp)
+    }
+
+    // The last session to start at or before a timestamp, they never overlap so it's the only one that can
+    // cover it
+    pub fn latest_before(&self, timestamp: f64) -> Option<&Session> {
+        let index = self
+            .sessions
+            .partition_point(|session| session.start as f64 <= timestamp);
+        index.checked_sub(1).map(|index| &self.sessions[index])
+    }
+
+    pub fn is_orphan_stop(&self, timestamp: i64) -> bool {
+        self.orphan_stops
+            .binary_search_by_key(&timestamp, |stop| stop.timestamp)
+            .is_ok()
+    }
+}
+
+// Sessions are paired per label, and only labels that got new start or stop logs are paired again, so a batch of
+// logs costs as much as the labels it touches rather than everything loaded.
+pub struct Sessions {
+    pub labels: HashMap<String, Label_Sessions>,
+    changed: HashSet<String>, // labels with marks that haven't been paired yet
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self {
+            labels: HashMap::new(),
+            changed: HashSet::new(),
+        }
+    }
+
+    // Sessions for a set of logs, with nothing missing between them and now
+    pub fn derive<'a>(logs: impl IntoIterator<Item = &'a Log>) -> Self {
+        let mut sessions = Self::new();
+        for log in logs {
+            sessions.insert(log);
+        }
+        sessions.update(|_| None);
+        sessions
+    }
+
+    // Takes note of a start or stop log, it's paired at the next update. Every log should only be inserted once.
+    pub fn insert(&mut self, log: &Log) {
+        let (label, is_stop) = match Log_Kind::of(log) {
+            Log_Kind::Start(label) => (label, false),
+            Log_Kind::Stop(label) => (label, true),
+            Log_Kind::Event => return,
+        };
+        let marks = &mut self
+            .labels
+            .entry(label.to_string())
+            .or_insert_with(Label_Sessions::new)
+            .marks;
+        let mark = (log.timestamp, is_stop);
+        let index = marks.partition_point(|other| *other <= mark);
+        marks.insert(index, mark);
+        self.changed.insert(label.to_string());
+    }
+
+    // Pairs the labels that changed, and works out again whether the open sessions are running. A session without
+    // a stop is only running if nothing between its start and now could be missing, gap_after gives where the
+    // loaded logs first run out after a timestamp, None if they reach now. Returns the spans to take out of the
+    // rect buffer and the ones to put in.
+    pub fn update(&mut self, gap_after: impl Fn(i64) -> Option<i64>) -> (Vec<Span>, Vec<Span>) {
+        let (mut removed, mut added) = (vec![], vec![]);
+        let changed = std::mem::take(&mut self.changed);
+        for (label, sessions) in &mut self.labels {
+            let repair = changed.contains(label);
+            let open = sessions
+                .sessions
+                .last()
+                .is_some_and(|session| !matches!(session.end, Session_End::Stopped(_)));
+            if !repair && !open {
+                continue;
+            }
+
+            let before = sessions.changing_spans(repair);
+            if repair {
+                sessions.pair(label);
+            }
+            if let Some(open) = sessions.open() {
+                open.end = match gap_after(open.start) {
+                    Some(gap) => Session_End::Unknown(gap),
+                    None => Session_End::Running,
+                };
+            }
+            let after = sessions.changing_spans(repair);
+
+            removed.extend(before.iter().filter(|span| !after.contains(span)).cloned());
+            added.extend(after.iter().filter(|span| !before.contains(span)).cloned());
+        }
+        (removed, added)
+    }
+
+    pub fn running(&self) -> impl Iterator<Item = &Session> {
+        self.labels
+            .values()
+            .filter_map(|sessions| sessions.sessions.last())
+            .filter(|session| session.is_running())
+    }
+
+    // Number of sessions and total time spent per label, running sessions count up to now
+    pub fn stats(&self, now: i64) -> Vec<Session_Stats> {
+        let mut stats: Vec<Session_Stats> = self
+            .labels
+            .iter()
+            .filter(|(_, sessions)| !sessions.sessions.is_empty())
+            .map(|(label, sessions)| Session_Stats {
+                label: label.clone(),
+                count: sessions.sessions.len(),
+                total_ms: sessions
+                    .sessions
+                    .iter()
+                    .map(|session| session.duration(now))
+                    .sum(),
+                running: sessions.sessions.iter().any(|session| session.is_running()),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.label.cmp(&b.label));
+        stats
+    }
+}
+
+// The span a session has in the rect buffer, None while it's running
+fn fixed_span(session: &Session) -> Option<Span> {
+    (!session.is_running()).then(|| {
+        (
+            session.start,
+            session.end_or(session.start),
+            session.label.clone(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(entries: &[(i64, &str)]) -> Vec<Log> {
+        entries
+            .iter()
+            .map(|(timestamp, label)| Log {
+                label: label.to_string(),
+                timestamp: *timestamp,
+            })
+            .collect()
+    }
+
+    fn derive(entries: &[(i64, &str)]) -> Sessions {
+        Sessions::derive(&logs(entries))
+    }
+
+    // every session, ordered by start
+    fn all(sessions: &Sessions) -> Vec<Session> {
+        let mut all: Vec<Session> = sessions
+            .labels
+            .values()
+            .flat_map(|label| label.sessions.iter().cloned())
+            .collect();
+        all.sort_by_key(|session| session.start);
+        all
+    }
+
+    fn orphans(sessions: &Sessions) -> Vec<Orphan_Stop> {
+        sessions
+            .labels
+            .values()
+            .flat_map(|label| label.orphan_stops.iter().cloned())
+            .collect()
+    }
+
+    fn session(label: &str, start: i64, end: Session_End) -> Session {
+        Session {
+            label: label.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn pairs_a_start_with_its_stop() {
+        // out of order on purpose, pairing goes by time
+        let sessions = derive(&[(30, "stop build"), (10, "start build"), (20, "deploy: ok")]);
+        assert_eq!(
+            all(&sessions),
+            vec![session("build", 10, Session_End::Stopped(30))]
+        );
+        assert!(orphans(&sessions).is_empty());
+    }
+
+    #[test]
+    fn unmatched_start_runs_until_now() {
+        let sessions = derive(&[(10, "start build")]);
+        let running = &all(&sessions)[0];
+        assert!(running.is_running());
+        assert_eq!(running.end_or(100), 100);
+        assert_eq!(running.duration(100), 90);
+        assert_eq!(sessions.running().count(), 1);
+    }
+
+    #[test]
+    fn labels_pair_separately() {
+        let sessions = derive(&[
+            (10, "start build"),
+            (20, "start test"),
+            (30, "stop build"),
+            (40, "stop test"),
+        ]);
+        assert_eq!(
+            all(&sessions),
+            vec![
+                session("build", 10, Session_End::Stopped(30)),
+                session("test", 20, Session_End::Stopped(40)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_without_a_start_is_an_orphan() {
+        let sessions = derive(&[(10, "stop build"), (20, "start build"), (30, "stop build")]);
+        assert_eq!(
+            orphans(&sessions),
+            vec![Orphan_Stop {
+                label: "build".to_string(),
+                timestamp: 10,
+            }]
+        );
+        assert_eq!(
+            all(&sessions),
+            vec![session("build", 20, Session_End::Stopped(30))]
+        );
+        assert!(sessions.labels["build"].is_orphan_stop(10));
+    }
+
+    #[test]
+    fn repeated_start_joins_the_running_session() {
+        let sessions = derive(&[
+            (10, "start build"),
+            (20, "start build"),
+            (30, "stop build"),
+            (40, "stop build"),
+        ]);
+        assert_eq!(
+            all(&sessions),
+            vec![session("build", 10, Session_End::Stopped(30))]
+        );
+        // the second stop has nothing left to close
+        assert_eq!(orphans(&sessions).len(), 1);
+        assert_eq!(orphans(&sessions)[0].timestamp, 40);
+    }
+
+    #[test]
+    fn open_sessions_are_unknown_until_everything_up_to_now_is_loaded() {
+        let mut sessions = Sessions::new();
+        for log in logs(&[(10, "start build"), (20, "start test"), (25, "stop test")]) {
+            sessions.insert(&log);
+        }
+        // logs are only loaded up to 50 after the build started
+        let (removed, added) = sessions.update(|start| (start == 10).then_some(50));
+        assert_eq!(
+            all(&sessions),
+            vec![
+                session("build", 10, Session_End::Unknown(50)),
+                session("test", 20, Session_End::Stopped(25)),
+            ]
+        );
+        assert_eq!(sessions.running().count(), 0);
+        assert!(removed.is_empty());
+        assert!(added.contains(&(10, 50, "build".to_string())));
+        assert!(added.contains(&(20, 25, "test".to_string())));
+
+        // once the rest is loaded the build is running, and its span leaves the rect buffer for the overlay
+        let (removed, added) = sessions.update(|_| None);
+        assert!(sessions.labels["build"].sessions[0].is_running());
+        assert_eq!(removed, vec![(10, 50, "build".to_string())]);
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn update_replaces_spans_that_changed() {
+        let mut sessions = Sessions::new();
+        for log in logs(&[(30, "stop build"), (40, "start test"), (50, "stop test")]) {
+            sessions.insert(&log);
+        }
+        sessions.update(|_| None);
+
+        // the start arrives later, the orphan stop turns into a session. Other labels are left alone.
+        for log in logs(&[(10, "start build")]) {
+            sessions.insert(&log);
+        }
+        let (removed, added) = sessions.update(|_| None);
+        assert_eq!(removed, vec![(30, 30, "build".to_string())]);
+        assert_eq!(added, vec![(10, 30, "build".to_string())]);
+        assert!(orphans(&sessions).is_empty());
+
+        // nothing new, nothing to do
+        assert_eq!(sessions.update(|_| None), (vec![], vec![]));
+    }
+
+    #[test]
+    fn stats_add_up_per_label() {
+        let sessions = derive(&[
+            (10, "start build"),
+            (20, "stop build"),
+            (30, "start build"),
+            (40, "start test"),
+            (45, "stop test"),
+        ]);
+        assert_eq!(
+            sessions.stats(100),
+            vec![
+                Session_Stats {
+                    label: "build".to_string(),
+                    count: 2,
+                    total_ms: 10 + 70,
+                    running: true,
+                },
+                Session_Stats {
+                    label: "test".to_string(),
+                    count: 1,
+                    total_ms: 5,
+                    running: false,
+                },
+            ]
+        );
+    }
+}
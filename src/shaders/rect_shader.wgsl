@@ -1,18 +1,39 @@
 struct Camera {
     position: f32,
     scale: f32,
-    padding: vec2<f32>,
+    y_offset: f32, // pixels the lane area is scrolled down by
+    y_scale: f32,  // vertical zoom of the lane area
+    screen: vec2<f32>, // canvas size in pixels
+    lanes_bottom: f32, // pixels from the top of the canvas where the lane area meets the ruler
+    padding: f32,
 }
 
+// Rects that aren't fixed are never drawn thinner than this, so instantaneous logs stay visible
+const MIN_WIDTH_PX: f32 = 2.0;
+
 @group(0) @binding(0)
 var<storage, read> colors: array<vec4<f32>>;
 @group(0) @binding(1)
 var<uniform> camera: Camera;
 
+// Where each lane currently sits on screen, indexed by the lane id in a rect's y
+struct Lane {
+    center: f32,
+    height: f32,
+    padding: vec2<f32>,
+}
+
+@group(0) @binding(2)
+var<storage, read> lanes: array<Lane>;
+
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color_index: f32,
+    @location(1) local: vec2<f32>, // position within the rect, 0 to 1
+    @location(2) @interpolate(flat) size_px: vec2<f32>,
+    @location(3) @interpolate(flat) outline: f32,
+    @location(4) @interpolate(flat) in_lanes: f32, // 1 for rects that belong to the lane area
 }
 
 @vertex
@@ -22,6 +43,7 @@ fn vs_main(
     @location(1) dimensions: vec2<f32>,
     @location(2) color_index: f32,
     @location(3) fixed: f32,
+    @location(4) outline: f32,
 ) -> VertexOutput {
     // The four corners of our rectangle
     var positions = array<vec2<f32>, 4>(
@@ -33,9 +55,18 @@ fn vs_main(
     
     let vertex_pos = positions[vertex_index]; // position of current vertex
     let camera_offset = vec2(camera.position/camera.scale, 0.0); // offset from camera
-    let instance_offset = position; // offset of instance 
+    var instance_offset = position; // offset of instance 
+    var size = dimensions;
+    if fixed == 0.0 {
+        // widths of timeline rects are durations in ms, so they scale with the camera like the offset does
+        size.x = max(dimensions.x / camera.scale, MIN_WIDTH_PX * 2.0 / camera.screen.x);
+        // vertical placement comes from the rect's lane, moved by the vertical camera from the top of the canvas
+        let lane = lanes[u32(position.y)];
+        instance_offset.y = 1.0 - (1.0 - lane.center) * camera.y_scale + camera.y_offset * 2.0 / camera.screen.y;
+        size.y = dimensions.y * lane.height * camera.y_scale;
+    }
     let scaled_instance_offset = vec2(instance_offset.x/camera.scale, instance_offset.y);
-    var screen_pos = (vertex_pos * dimensions); // screen space position
+    var screen_pos = (vertex_pos * size); // screen space position
     
     if fixed == 0.0 {
         screen_pos += scaled_instance_offset  - camera_offset;
@@ -43,12 +74,29 @@ fn vs_main(
     var output: VertexOutput;
     output.position = vec4<f32>(screen_pos, 0.0, 1.0);
     output.color_index = color_index;
+    output.local = vertex_pos + 0.5;
+    output.size_px = size * camera.screen / 2.0;
+    output.outline = outline;
+    output.in_lanes = select(0.0, 1.0, fixed == 0.0);
     
     return output;
 }
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Lanes scrolled down would otherwise run over the ruler
+    if input.in_lanes > 0.0 && input.position.y > camera.lanes_bottom {
+        discard;
+    }
+
+    // Outlined rects only keep the pixels near their edges
+    if input.outline > 0.0 {
+        let from_edge = min(input.local, 1.0 - input.local) * input.size_px;
+        if min(from_edge.x, from_edge.y) > input.outline {
+            discard;
+        }
+    }
+
     // Get the color from the color buffer using the color index
     let color_idx = u32(input.color_index);
     let color = colors[color_idx];
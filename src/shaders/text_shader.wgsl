@@ -0,0 +1,71 @@
+struct Camera {
+    position: f32,
+    scale: f32,
+    y_offset: f32, // pixels the lane area is scrolled down by
+    y_scale: f32,  // vertical zoom of the lane area
+    screen: vec2<f32>, // canvas size in pixels
+    lanes_bottom: f32,
+    padding: f32,
+}
+
+@group(0) @binding(0)
+var<storage, read> colors: array<vec4<f32>>;
+@group(0) @binding(1)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color_index: f32,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec2<f32>,
+    @location(1) offset: vec2<f32>,
+    @location(2) dimensions: vec2<f32>,
+    @location(3) uv_position: vec2<f32>,
+    @location(4) uv_dimensions: vec2<f32>,
+    @location(5) color_index: f32,
+    @location(6) fixed: f32,
+) -> VertexOutput {
+    // The four corners of our glyph quad, same order as the rect shader
+    var positions = array<vec2<f32>, 4>(
+        vec2<f32>(-0.5, -0.5), // Bottom-left
+        vec2<f32>( 0.5, -0.5), // Bottom-right
+        vec2<f32>( 0.5,  0.5), // Top-right
+        vec2<f32>(-0.5,  0.5)  // Top-left
+    );
+
+    let vertex_pos = positions[vertex_index]; // position of current vertex
+    var anchor = position; // start of the baseline, screen space if fixed
+    if fixed == 0.0 {
+        anchor.x = (position.x - camera.position) / camera.scale;
+    }
+    // glyph offsets and sizes are in pixels so text stays the same size no matter the zoom
+    let pixel_pos = offset + vertex_pos * dimensions;
+    let screen_pos = anchor + pixel_pos * 2.0 / camera.screen;
+
+    var output: VertexOutput;
+    output.position = vec4<f32>(screen_pos, 0.0, 1.0);
+    // texture v runs down while our y runs up
+    output.uv = uv_position + vec2<f32>(vertex_pos.x + 0.5, 0.5 - vertex_pos.y) * uv_dimensions;
+    output.color_index = color_index;
+
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, input.uv).r;
+    let color = colors[u32(input.color_index)];
+
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
@@ -0,0 +1,254 @@
+use std::ops::Range;
+
+use crate::gpu_resources::RECT_FLOATS;
+
+// Rects are culled in blocks of this many. A long span only keeps its own block on screen, not everything after
+// it, at the cost of drawing up to a block of rects that are just off screen.
+const BLOCK_SIZE: usize = 256;
+
+// CPU copy of the rect buffer, kept ordered by where each rect starts so the renderer can find the rects that
+// are on screen without looking at each one. Positions are also kept as absolute timestamps in f64, so the
+// f32 offsets on the gpu can be recomputed exactly whenever the origin moves.
+pub struct Sorted_Rects {
+    pub instances: Vec<f32>, // RECT_FLOATS per rect, same layout as the gpu buffer
+    pub origin: i64,         // timestamp the x of each instance is relative to
+    centers: Vec<f64>,       // timestamp at the center of each rect
+    starts: Vec<f64>,        // timestamp at the left edge of each rect
+    block_ends: Vec<f64>,    // furthest right edge of any rect in each BLOCK_SIZE run of rects
+}
+
+// Left and right edges of a rect instance centered on a timestamp. Fixed rects are always on screen, so they
+// span everything.
+fn extent(instance: &[f32], center: f64) -> (f64, f64) {
+    let (w, fixed) = (instance[2] as f64, instance[5]);
+    if fixed != 0.0 {
+        (f64::NEG_INFINITY, f64::INFINITY)
+    } else {
+        (center - w / 2.0, center + w / 2.0)
+    }
+}
+
+impl Sorted_Rects {
+    pub fn new(origin: i64) -> Self {
+        Self {
+            instances: vec![],
+            origin,
+            centers: vec![],
+            starts: vec![],
+            block_ends: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    // Merges new rects in, each with the timestamp at its center. Returns the index of the first rect that moved
+    // or was added, everything before it is unchanged so only the rest needs uploading.
+    pub fn insert(&mut self, rects: Vec<([f32; RECT_FLOATS], f64)>) -> usize {
+        let mut new = rects;
+        if new.is_empty() {
+            return self.len();
+        }
+        let start_of = |(instance, center): &([f32; RECT_FLOATS], f64)| extent(instance, *center).0;
+        new.sort_by(|a, b| start_of(a).total_cmp(&start_of(b)));
+
+        // new logs mostly land at the end of the timeline, in which case nothing existing moves
+        let first_start = start_of(&new[0]);
+        let first = self.starts.partition_point(|start| *start <= first_start);
+        let mut tail: Vec<([f32; RECT_FLOATS], f64)> = self.instances[first * RECT_FLOATS..]
+            .chunks_exact(RECT_FLOATS)
+            .zip(&self.centers[first..])
+            .map(|(instance, center)| (instance.try_into().unwrap(), *center))
+            .collect();
+        self.truncate(first);
+
+        // both runs are sorted, old rects go first on ties so equal rects keep their order
+        tail.reverse();
+        new.reverse();
+        loop {
+            let rect = match (tail.last(), new.last()) {
+                (Some(old), Some(added)) => {
+                    if start_of(old) <= start_of(added) {
+                        tail.pop()
+                    } else {
+                        new.pop()
+                    }
+                }
+                (Some(_), None) => tail.pop(),
+                (None, Some(_)) => new.pop(),
+                (None, None) => break,
+            };
+            let (instance, center) = rect.unwrap();
+            self.push(instance, center);
+        }
+        first
+    }
+
+    // Takes rects back out, each given the same way it was inserted. Rects that aren't there are ignored.
+    // Returns the index of the first rect that moved, like insert.
+    pub fn remove(&mut self, rects: Vec<([f32; RECT_FLOATS], f64)>) -> usize {
+        let mut removed = vec![false; self.len()];
+        for (instance, center) in rects {
+            let start = extent(&instance, center).0;
+            let first = self
+                .starts
+                .partition_point(|rect_start| *rect_start < start);
+            let last = self
+                .starts
+                .partition_point(|rect_start| *rect_start <= start);
+            // x is left out, it depends on the origin the rect was made relative to
+            let found = (first..last).find(|index| {
+                !removed[*index]
+                    && self.centers[*index] == center
+                    && self.instances[index * RECT_FLOATS + 1..(index + 1) * RECT_FLOATS]
+                        == instance[1..]
+            });
+            if let Some(index) = found {
+                removed[index] = true;
+            }
+        }
+        let Some(first) = removed.iter().position(|removed| *removed) else {
+            return self.len();
+        };
+
+        let tail: Vec<([f32; RECT_FLOATS], f64)> = self.instances[first * RECT_FLOATS..]
+            .chunks_exact(RECT_FLOATS)
+            .zip(&self.centers[first..])
+            .zip(&removed[first..])
+            .filter(|(_, removed)| !**removed)
+            .map(|((instance, center), _)| (instance.try_into().unwrap(), *center))
+            .collect();
+        self.truncate(first);
+        for (instance, center) in tail {
+            self.push(instance, center);
+        }
+        first
+    }
+
+    fn push(&mut self, instance: [f32; RECT_FLOATS], center: f64) {
+        let (start, end) = extent(&instance, center);
+        if self.len() % BLOCK_SIZE == 0 {
+            self.block_ends.push(end);
+        } else {
+            let block_end = self.block_ends.last_mut().unwrap();
+            *block_end = block_end.max(end);
+        }
+        self.instances.extend_from_slice(&instance);
+        self.centers.push(center);
+        self.starts.push(start);
+    }
+
+    // Drops every rect from len on, the block that's cut in half gets its end worked out again
+    fn truncate(&mut self, len: usize) {
+        let block_start = len / BLOCK_SIZE * BLOCK_SIZE;
+        let kept: Vec<([f32; RECT_FLOATS], f64)> = self.instances
+            [block_start * RECT_FLOATS..len * RECT_FLOATS]
+            .chunks_exact(RECT_FLOATS)
+            .zip(&self.centers[block_start..len])
+            .map(|(instance, center)| (instance.try_into().unwrap(), *center))
+            .collect();
+        self.instances.truncate(block_start * RECT_FLOATS);
+        self.centers.truncate(block_start);
+        self.starts.truncate(block_start);
+        self.block_ends.truncate(block_start / BLOCK_SIZE);
+        for (instance, center) in kept {
+            self.push(instance, center);
+        }
+    }
+
+    // Makes every rect relative to a new origin
+    pub fn rebase(&mut self, origin: i64) {
+        self.origin = origin;
+        for (instance, center) in self
+            .instances
+            .chunks_exact_mut(RECT_FLOATS)
+            .zip(&self.centers)
+        {
+            if instance[5] == 0.0 {
+                instance[0] = (center - origin as f64) as f32;
+            }
+        }
+    }
+
+    // Runs of rects that include every rect overlapping the timestamps from start to end, in order
+    pub fn visible(&self, start: f64, end: f64) -> Vec<Range<usize>> {
+        let last = self.starts.partition_point(|rect_start| *rect_start <= end);
+        let mut runs: Vec<Range<usize>> = vec![];
+        for (block, block_end) in self.block_ends.iter().enumerate() {
+            let first = block * BLOCK_SIZE;
+            if first >= last {
+                break;
+            }
+            if *block_end < start {
+                continue;
+            }
+            let run_end = (first + BLOCK_SIZE).min(last);
+            match runs.last_mut() {
+                Some(run) if run.end == first => run.end = run_end,
+                _ => runs.push(first..run_end),
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a rect covering start to end, the way span rects are made
+    fn span(start: f64, end: f64) -> ([f32; RECT_FLOATS], f64) {
+        let center = (start + end) / 2.0;
+        (
+            [center as f32, 0.0, (end - start) as f32, 1.0, 0.0, 0.0, 0.0],
+            center,
+        )
+    }
+
+    fn rects(spans: impl IntoIterator<Item = (f64, f64)>) -> Sorted_Rects {
+        let mut rects = Sorted_Rects::new(0);
+        rects.insert(
+            spans
+                .into_iter()
+                .map(|(start, end)| span(start, end))
+                .collect(),
+        );
+        rects
+    }
+
+    fn visible_count(rects: &Sorted_Rects, start: f64, end: f64) -> usize {
+        rects.visible(start, end).iter().map(|run| run.len()).sum()
+    }
+
+    #[test]
+    fn culls_around_a_long_span() {
+        // one span covering everything, then lots of short rects
+        let mut spans = vec![(0.0, 1_000_000.0)];
+        spans.extend((0..10_000).map(|i| (i as f64 * 100.0, i as f64 * 100.0 + 10.0)));
+        let rects = rects(spans);
+
+        // the long span's block and the blocks near the view, rather than everything up to the view
+        let runs = rects.visible(500_000.0, 500_050.0);
+        assert_eq!(runs.first().unwrap().start, 0);
+        assert!(visible_count(&rects, 500_000.0, 500_050.0) <= BLOCK_SIZE * 3);
+        let drawn: Vec<usize> = runs.iter().flat_map(|run| run.clone()).collect();
+        for (index, instance) in rects.instances.chunks_exact(RECT_FLOATS).enumerate() {
+            let (start, end) = extent(instance, rects.centers[index]);
+            if end >= 500_000.0 && start <= 500_050.0 {
+                assert!(drawn.contains(&index));
+            }
+        }
+    }
+
+    #[test]
+    fn block_ends_survive_removal() {
+        let mut rects = rects((0..1000).map(|i| (i as f64 * 10.0, i as f64 * 10.0 + 5.0)));
+        // a long span early on keeps its block on screen until it's taken back out
+        rects.insert(vec![span(100.0, 50_000.0)]);
+        assert!(visible_count(&rects, 9000.0, 9100.0) > BLOCK_SIZE);
+        rects.remove(vec![span(100.0, 50_000.0)]);
+        assert_eq!(rects.len(), 1000);
+        assert!(visible_count(&rects, 9000.0, 9100.0) <= BLOCK_SIZE * 2);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::log_store::Log_Key;
+
+// Everything needed to bring someone else to the same view, stored in the url hash as
+// t=<center ms>&s=<scale ms>&sel=<timestamp>~<label>&collapsed=<lane>,<lane>
+#[derive(Clone, Debug, PartialEq)]
+pub struct View_State {
+    pub pos: f64,
+    pub scale: f64,
+    pub selected: Option<Log_Key>,
+    pub collapsed: Vec<String>, // names of lanes that are folded away
+}
+
+impl View_State {
+    pub fn encode(&self) -> String {
+        // whole milliseconds are plenty, and keep links short
+        let mut hash = format!(
+            "t={}&s={}",
+            self.pos.round() as i64,
+            self.scale.round() as i64
+        );
+        if let Some((timestamp, label)) = &self.selected {
+            hash.push_str(&format!("&sel={}~{}", timestamp, percent_encode(label)));
+        }
+        if !self.collapsed.is_empty() {
+            let names: Vec<String> = self
+                .collapsed
+                .iter()
+                .map(|name| percent_encode(name))
+                .collect();
+            hash.push_str(&format!("&collapsed={}", names.join(",")));
+        }
+        hash
+    }
+
+    // Anything missing or malformed is left out rather than failing the whole thing, None if there's no view
+    pub fn decode(hash: &str) -> Option<Self> {
+        let hash = hash.strip_prefix('#').unwrap_or(hash);
+        let (mut pos, mut scale, mut selected, mut collapsed) = (None, None, None, vec![]);
+        for pair in hash.split('&') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            match key {
+                "t" => pos = value.parse::<f64>().ok().filter(|pos| pos.is_finite()),
+                "s" => {
+                    scale = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|scale| scale.is_finite() && *scale > 0.0)
+                }
+                "sel" => {
+                    selected = value.split_once('~').and_then(|(timestamp, label)| {
+                        Some((timestamp.parse::<i64>().ok()?, percent_decode(label)?))
+                    })
+                }
+                "collapsed" => {
+                    collapsed = value
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .filter_map(percent_decode)
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            pos: pos?,
+            scale: scale?,
+            selected,
+            collapsed,
+        })
+    }
+}
+
+// Escapes everything but unreserved characters, so labels can't break up the hash
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // from_str_radix would also take a sign, like "%+1"
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(selected: Option<(i64, &str)>, collapsed: &[&str]) -> View_State {
+        View_State {
+            pos: 1_700_000_000_000.0,
+            scale: 30_000.0,
+            selected: selected.map(|(timestamp, label)| (timestamp, label.to_string())),
+            collapsed: collapsed.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let plain = view(None, &[]);
+        assert_eq!(View_State::decode(&plain.encode()), Some(plain));
+
+        // labels and lane names can hold anything the hash uses as a separator
+        let awkward = view(
+            Some((1_700_000_000_123, "start db: a&b=c~d, 100% done")),
+            &["net,work", "ünïcode lane", "#"],
+        );
+        assert_eq!(View_State::decode(&awkward.encode()), Some(awkward));
+    }
+
+    #[test]
+    fn decodes_with_or_without_the_hash_sign() {
+        let state = view(Some((5, "a")), &["b"]);
+        assert_eq!(
+            View_State::decode(&format!("#{}", state.encode())),
+            Some(state)
+        );
+    }
+
+    #[test]
+    fn missing_position_or_scale_is_no_view() {
+        assert_eq!(View_State::decode(""), None);
+        assert_eq!(View_State::decode("t=100"), None);
+        assert_eq!(View_State::decode("s=100"), None);
+        assert_eq!(View_State::decode("t=&s=100"), None);
+    }
+
+    #[test]
+    fn rejects_numbers_that_arent_finite() {
+        assert_eq!(View_State::decode("t=NaN&s=100"), None);
+        assert_eq!(View_State::decode("t=inf&s=100"), None);
+        assert_eq!(View_State::decode("t=100&s=inf"), None);
+        assert_eq!(View_State::decode("t=100&s=NaN"), None);
+        assert_eq!(View_State::decode("t=100&s=0"), None);
+        assert_eq!(View_State::decode("t=100&s=-5"), None);
+    }
+
+    #[test]
+    fn drops_fields_with_bad_escapes() {
+        let state = View_State::decode("t=100&s=50&sel=5~bad%zz&collapsed=a,b%2,c%2C,%+1").unwrap();
+        assert_eq!(state.selected, None);
+        assert_eq!(state.collapsed, vec!["a".to_string(), "c,".to_string()]);
+
+        // escapes that decode to invalid UTF-8 are as bad as malformed ones
+        let state = View_State::decode("t=100&s=50&sel=5~%FF").unwrap();
+        assert_eq!(state.selected, None);
+    }
+
+    #[test]
+    fn ignores_unknown_keys_and_stray_pairs() {
+        let state = View_State::decode("t=100&zoom=3&s=50&junk&sel=nope&=x").unwrap();
+        assert_eq!(
+            state,
+            View_State {
+                pos: 100.0,
+                scale: 50.0,
+                selected: None,
+                collapsed: vec![],
+            }
+        );
+    }
+}
@@ -0,0 +1,52 @@
+import { createSignal, Show } from "solid-js";
+
+export type SelectedLog = {
+	timestamp: number;
+	time: string;
+	label: string;
+	start: number;
+	end: number;
+	duration: number;
+};
+
+const [selected, setSelected] = createSignal<SelectedLog | null>(null);
+
+// Ask the wasm module to tell us whenever a log is clicked on the timeline.
+export function listenForSelection() {
+	window.wasmBindings.set_selection_callback((log: SelectedLog | null) => setSelected(log));
+}
+
+function formatDuration(ms: number): string {
+	const seconds = Math.floor(ms / 1000);
+	const parts = [
+		[Math.floor(seconds / 86400), "d"],
+		[Math.floor(seconds / 3600) % 24, "h"],
+		[Math.floor(seconds / 60) % 60, "m"],
+		[seconds % 60, "s"],
+	].filter(([value]) => value != 0);
+
+	return parts.length ? parts.map(([value, unit]) => `${value}${unit}`).join(" ") : `${ms}ms`;
+}
+
+export function Details() {
+	return (
+		<Show when={selected()}>
+			{(log) => (
+				<div class="Details">
+					<h2>{log().label}</h2>
+					<dl>
+						<dt>Time</dt>
+						<dd>{log().time}</dd>
+						<dt>Timestamp</dt>
+						<dd>{log().timestamp}</dd>
+						<Show when={log().duration > 0}>
+							<dt>Duration</dt>
+							<dd>{formatDuration(log().duration)}</dd>
+						</Show>
+					</dl>
+					<button onClick={() => window.wasmBindings.clear_selection()}>Close</button>
+				</div>
+			)}
+		</Show>
+	);
+}
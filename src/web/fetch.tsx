@@ -1,14 +1,56 @@
-export async function loadLogs() {
-    let url = "http://35.155.119.40";
-    // let url = "localhost:8088";
-    let endpoint = "logLoad";
+let url = "http://35.155.119.40";
+// let url = "localhost:8088";
+
+// Whether the backend's logLoad takes start and end. Until it does, a range request loads everything instead and
+// all time up to now counts as loaded, so the timeline stops asking.
+const RANGE_REQUESTS = false;
+
+// The timeline asks for the logs around wherever it's looking, and tells us as the view moves on to new ranges.
+// Whatever was cached last time is shown first, so only what's missing from it gets requested.
+export function listenForRangeRequests() {
+    window.wasmBindings.open_cache(() => {
+        window.wasmBindings.set_range_request_callback((start: number, end: number) => {
+            loadRange(start, end);
+        });
+    });
+}
+
+async function loadRange(start: number, end: number) {
+    if (!RANGE_REQUESTS) {
+        return loadEverything(start, end);
+    }
+    let endpoint = `logLoad?start=${start}&end=${end}`;
 
     try {
         const byteArray = await fetchByteArray(url, endpoint);
-	window.wasmBindings.receive_body(byteArray);
+        window.wasmBindings.receive_range(byteArray, start, end);
+    } catch (error) {
+        console.error(`Error fetching logs from ${start} to ${end}:`, error);
+        window.wasmBindings.range_failed(start, end);
+    }
+}
+
+// The first requests of a burst share one fetch of everything, the rest of them just count as loaded
+let everything: Promise<Uint8Array> | null = null;
+
+async function loadEverything(start: number, end: number) {
+    const first = everything == null;
+    if (first) {
+        everything = fetchByteArray(url, "logLoad").finally(() => {
+            everything = null;
+        });
+    }
 
+    try {
+        const byteArray = await everything!;
+        if (first) {
+            window.wasmBindings.receive_all(byteArray);
+        } else {
+            window.wasmBindings.receive_range(new Uint8Array(), start, end);
+        }
     } catch (error) {
-        console.error("Error fetching data:", error);
+        console.error("Error fetching logs:", error);
+        window.wasmBindings.range_failed(start, end);
     }
 }
 
@@ -29,4 +71,3 @@ export async function fetchByteArray(url: string, endpoint: string): Promise<Uin
         throw error;
     }
 }
-
@@ -0,0 +1,22 @@
+import { createSignal } from "solid-js";
+
+const [following, setFollowing] = createSignal(false);
+
+// The timeline stops following on its own when it's panned away, so it tells us rather than us keeping track.
+export function listenForFollow() {
+	setFollowing(window.wasmBindings.is_following());
+	window.wasmBindings.set_follow_callback((follow: boolean) => setFollowing(follow));
+}
+
+export function FollowButton() {
+	return (
+		<button
+			class="FollowButton"
+			classList={{ active: following() }}
+			title="Keep the current time in view (F)"
+			onClick={() => window.wasmBindings.set_follow(!following())}
+		>
+			{following() ? "Following now" : "Follow now"}
+		</button>
+	);
+}
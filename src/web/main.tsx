@@ -1,16 +1,27 @@
 import { render } from "solid-js/web";
-import { loadLogs } from "./fetch.tsx";
+import { listenForRangeRequests } from "./fetch.tsx";
+import { streamLogs } from "./stream.tsx";
+import { Details, listenForSelection } from "./details.tsx";
+import { syncViewState } from "./view_state.tsx";
+import { FollowButton, listenForFollow } from "./follow.tsx";
+import { ParseErrors, listenForParseErrors } from "./parse_errors.tsx";
 
 export function main() {
-	loadLogs();
+	listenForSelection();
+	listenForParseErrors();
+	syncViewState();
+	listenForFollow();
+	listenForRangeRequests();
+	streamLogs();
 
-	// function App() {
-	// 	return (
-	// 		<div class="HelloWorld">
-	// 			<h1>Hello, World! </h1>
-	// 		</div>
-	// 	);
-	// }
-
-	// render(() => <App />, document.getElementById("root") as HTMLElement);
+	render(
+		() => (
+			<>
+				<FollowButton />
+				<Details />
+				<ParseErrors />
+			</>
+		),
+		document.getElementById("root") as HTMLElement,
+	);
 }
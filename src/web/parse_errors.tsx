@@ -0,0 +1,63 @@
+import { createSignal, For, Show } from "solid-js";
+
+export type ParseError = {
+	line: number;
+	reason: string;
+	text: string;
+};
+
+export type ParseReport = {
+	source: string;
+	lines: number;
+	skipped: number;
+	errors: ParseError[]; // only the first few when lots of lines were skipped
+};
+
+// the stream can keep sending bad lines, only the latest reports are kept
+const MAX_REPORTS = 20;
+
+const [reports, setReports] = createSignal<ParseReport[]>([]);
+
+// Ask the wasm module to tell us whenever lines of logs couldn't be read and had to be skipped.
+export function listenForParseErrors() {
+	window.wasmBindings.set_parse_error_callback((report: ParseReport) => {
+		console.warn(`Skipped ${report.skipped} of ${report.lines} lines from ${report.source}`, report.errors);
+		setReports([...reports(), report].slice(-MAX_REPORTS));
+	});
+}
+
+export function ParseErrors() {
+	const skipped = () => reports().reduce((total, report) => total + report.skipped, 0);
+
+	return (
+		<Show when={reports().length > 0}>
+			<div class="ParseErrors">
+				<h2>
+					{skipped()} {skipped() == 1 ? "log" : "logs"} couldn't be read
+				</h2>
+				<For each={reports()}>
+					{(report) => (
+						<details>
+							<summary>
+								{report.skipped} of {report.lines} lines from {report.source}
+							</summary>
+							<ul>
+								<For each={report.errors}>
+									{(error) => (
+										<li>
+											<span class="reason">
+												line {error.line}: {error.reason}
+											</span>
+											<code>{error.text}</code>
+										</li>
+									)}
+								</For>
+							</ul>
+						</details>
+					)}
+				</For>
+				<button onClick={() => setReports([])}>Dismiss</button>
+			</div>
+		</Show>
+	);
+}
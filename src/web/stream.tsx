@@ -0,0 +1,62 @@
+// Reconnect attempts wait twice as long as the last one, up to a limit, so a backend that's down isn't hammered
+const MIN_RETRY_MS = 1000;
+const MAX_RETRY_MS = 30000;
+
+// Keeps a WebSocket open to the backend and hands every log it pushes to the timeline as it arrives.
+export function streamLogs() {
+	let url = "ws://35.155.119.40/logStream";
+	// let url = "ws://localhost:8089"; // dev/stream_server.mjs
+
+	let retry = MIN_RETRY_MS;
+	let disconnected_at: number | null = null;
+	// lines that arrived since the last frame, handed over together so a burst of logs is one update
+	let pending: string[] = [];
+	let flush_scheduled = false;
+	const decoder = new TextDecoder();
+
+	function flush() {
+		flush_scheduled = false;
+		if (pending.length == 0) return;
+		const body = pending.join("\n");
+		pending = [];
+		window.wasmBindings.receive_text(body);
+	}
+
+	function connect() {
+		const socket = new WebSocket(url);
+		socket.binaryType = "arraybuffer";
+
+		socket.onopen = () => {
+			retry = MIN_RETRY_MS;
+			// anything sent while we were disconnected was missed, so that stretch is fetched again when it's in
+			// view. Logs we already have are ignored.
+			if (disconnected_at != null) window.wasmBindings.forget_loaded_after(disconnected_at);
+			disconnected_at = null;
+			window.wasmBindings.set_stream_connected(true);
+		};
+
+		socket.onmessage = (event) => {
+			const text = typeof event.data == "string" ? event.data : decoder.decode(event.data);
+			for (const line of text.split("\n")) {
+				if (line.trim() != "") pending.push(line);
+			}
+			if (!flush_scheduled) {
+				flush_scheduled = true;
+				requestAnimationFrame(flush);
+			}
+		};
+
+		// onerror is always followed by onclose, so reconnecting is only handled here
+		socket.onclose = () => {
+			disconnected_at ??= Date.now();
+			window.wasmBindings.set_stream_connected(false);
+			// a little randomness keeps every open tab from reconnecting at the same moment
+			const delay = retry * (0.75 + Math.random() * 0.5);
+			console.warn(`Log stream closed, reconnecting in ${Math.round(delay)}ms`);
+			retry = Math.min(retry * 2, MAX_RETRY_MS);
+			setTimeout(connect, delay);
+		};
+	}
+
+	connect();
+}
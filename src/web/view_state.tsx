@@ -0,0 +1,14 @@
+// Keeps the url hash in step with the timeline, so a link brings whoever opens it to the same view.
+export function syncViewState() {
+	window.wasmBindings.restore_view_state(window.location.hash);
+
+	// replaceState rather than assigning the hash, so panning around doesn't fill up the back button history
+	window.wasmBindings.set_view_state_callback((hash: string) => {
+		history.replaceState(null, "", `#${hash}`);
+	});
+
+	// a link pasted into a tab that already has the hub open
+	window.addEventListener("hashchange", () => {
+		window.wasmBindings.restore_view_state(window.location.hash);
+	});
+}